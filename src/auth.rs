@@ -0,0 +1,371 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use console::Term;
+use serde::{Deserialize, Serialize};
+use twitch_api::twitch_oauth2::client::Client;
+use twitch_api::twitch_oauth2::tokens::errors::ValidationError;
+use twitch_api::twitch_oauth2::{AccessToken, RefreshToken, Scope, TwitchToken, UserToken};
+
+use crate::{config, Error, Result};
+
+/// The default root of the Twitch OAuth endpoints, overridable with `TWITCH_OAUTH2_URL`
+pub const DEFAULT_AUTH_BASE_URL: &str = "https://id.twitch.tv/oauth2/";
+
+/// The scopes every command needs
+pub const SCOPES: &[Scope] = &[Scope::ChannelManagePredictions];
+
+/// The scopes `bot` additionally needs to read and reply in chat, also requested when logging in
+pub const CHAT_SCOPES: &[Scope] = &[Scope::ChatRead, Scope::ChatEdit];
+
+/// Tokens expiring within this margin are refreshed before being used
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// A token obtained through `login`, persisted between runs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredToken {
+    pub client_id: String,
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub login: String,
+    pub user_id: String,
+}
+
+impl StoredToken {
+    /// The file the token is stored in
+    pub fn path() -> Result<PathBuf> {
+        Ok(config::config_dir()?.join("token.json"))
+    }
+
+    /// Loads the stored token, returning `None` if nobody has logged in yet
+    pub fn load() -> Result<Option<StoredToken>> {
+        Self::load_from(&Self::path()?)
+    }
+
+    /// Loads the token stored at `path`, returning `None` if there is none
+    pub fn load_from(path: &Path) -> Result<Option<StoredToken>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(Error::Storage(format!(
+                    "Unable to read token from {}: {e}",
+                    path.display()
+                )))
+            }
+        };
+
+        let token = serde_json::from_str(&contents).map_err(|e| {
+            Error::Storage(format!("Unable to parse token in {}: {e}", path.display()))
+        })?;
+
+        Ok(Some(token))
+    }
+
+    /// Writes the token to disk, readable and writable by the current user only
+    pub fn save(&self) -> Result<PathBuf> {
+        let path = Self::path()?;
+        self.save_to(&path)?;
+
+        Ok(path)
+    }
+
+    /// Like [`StoredToken::save`], to the file at `path`
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        self.write(path).map_err(|e| {
+            Error::Storage(format!("Unable to write token to {}: {e}", path.display()))
+        })
+    }
+
+    fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // The mode only applies to newly created files
+            if path.exists() {
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+
+        let mut file = options.open(path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())
+    }
+
+    /// Validates the stored token, turning it into a [`UserToken`] usable for requests
+    ///
+    /// Access tokens only last a few hours, so a token Twitch no longer accepts is refreshed
+    /// first if there is a refresh token, storing the new token at `path` for later runs.
+    pub async fn to_user_token<C: Client>(&self, client: &C, path: &Path) -> Result<UserToken> {
+        match self.validate(client).await {
+            Err(ValidationError::NotAuthorized) if self.refresh_token.is_some() => {}
+            result => return Ok(result?),
+        }
+
+        let refreshed = self.refresh().await?;
+        refreshed.save_to(path)?;
+
+        Ok(refreshed.validate(client).await?)
+    }
+
+    async fn validate<C: Client>(
+        &self,
+        client: &C,
+    ) -> std::result::Result<UserToken, ValidationError<C::Error>> {
+        UserToken::from_existing(
+            client,
+            AccessToken::new(self.access_token.clone()),
+            self.refresh_token.clone().map(RefreshToken::new),
+            None,
+        )
+        .await
+    }
+
+    /// Exchanges the refresh token for a new token with the refresh token grant
+    async fn refresh(&self) -> Result<StoredToken> {
+        let Some(ref refresh_token) = self.refresh_token else {
+            return Err(Error::InvalidToken(format!(
+                "The token for {} has expired and can't be refreshed",
+                self.login
+            )));
+        };
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/token", auth_base_url()))
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .send()
+            .await
+            .map_err(network)?;
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(Error::RateLimited);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = error_message(response).await;
+            return Err(Error::InvalidToken(format!(
+                "Unable to refresh the token for {} ({status}): {message}",
+                self.login
+            )));
+        }
+        let refreshed: TokenResponse = response.json().await.map_err(network)?;
+
+        Ok(StoredToken {
+            client_id: self.client_id.clone(),
+            access_token: refreshed.access_token,
+            refresh_token: refreshed.refresh_token.or(Some(refresh_token.clone())),
+            login: self.login.clone(),
+            user_id: self.user_id.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    expires_in: u64,
+    interval: u64,
+    user_code: String,
+    verification_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// Returns the root of the Twitch OAuth endpoints, as configured in `main`
+fn auth_base_url() -> String {
+    env::var("TWITCH_OAUTH2_URL")
+        .unwrap_or_else(|_| DEFAULT_AUTH_BASE_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+async fn error_message(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .map(|e| e.message)
+        .unwrap_or_default()
+}
+
+fn network(error: reqwest::Error) -> Error {
+    Error::Network(Box::new(error))
+}
+
+fn scopes_param() -> String {
+    SCOPES
+        .iter()
+        .chain(CHAT_SCOPES)
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Logs in with the OAuth Device Code Grant flow
+///
+/// The user is asked to open the verification URL in a browser while we poll
+/// for the token, which is then stored on disk.
+pub async fn login(term: &mut Term, base_url: &str, client_id: &str) -> Result<StoredToken> {
+    let client = reqwest::Client::new();
+    let base_url = base_url.trim_end_matches('/');
+
+    let response = client
+        .post(format!("{base_url}/device"))
+        .form(&[("client_id", client_id), ("scopes", &scopes_param())])
+        .send()
+        .await
+        .map_err(network)?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let message = error_message(response).await;
+        return Err(Error::Login(format!(
+            "Unable to start device login ({status}): {message}"
+        )));
+    }
+    let device: DeviceCodeResponse = response.json().await.map_err(network)?;
+
+    writeln!(
+        term,
+        "Open {} and enter the code {}",
+        console::style(&device.verification_uri).underlined(),
+        console::style(&device.user_code).bold()
+    )?;
+
+    let mut interval = Duration::from_secs(device.interval.max(1));
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(device.expires_in);
+
+    let token = loop {
+        if tokio::time::Instant::now() >= deadline {
+            return Err(Error::Login(
+                "The device code expired before the login was completed".to_string(),
+            ));
+        }
+        tokio::time::sleep(interval).await;
+
+        let response = client
+            .post(format!("{base_url}/token"))
+            .form(&[
+                ("client_id", client_id),
+                ("scopes", &scopes_param()),
+                ("device_code", &device.device_code),
+                ("grant_type", DEVICE_CODE_GRANT_TYPE),
+            ])
+            .send()
+            .await
+            .map_err(network)?;
+
+        if response.status().is_success() {
+            break response.json::<TokenResponse>().await.map_err(network)?;
+        }
+
+        let status = response.status();
+        let message = error_message(response).await;
+        match message.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += Duration::from_secs(5),
+            _ => return Err(Error::Login(format!("{message} ({status})"))),
+        }
+    };
+
+    let validated = UserToken::from_existing(
+        &client,
+        AccessToken::new(token.access_token.clone()),
+        None,
+        None,
+    )
+    .await?;
+
+    let stored = StoredToken {
+        client_id: client_id.to_string(),
+        access_token: token.access_token,
+        refresh_token: token.refresh_token,
+        login: validated.login.to_string(),
+        user_id: validated.user_id.to_string(),
+    };
+
+    Ok(stored)
+}
+
+/// Fails with a list of the missing scopes if `token` lacks any scope we need
+pub fn check_scopes(token: &UserToken) -> Result<()> {
+    require_scopes(token, SCOPES)
+}
+
+/// Like [`check_scopes`], for the scopes needed in chat
+pub fn check_chat_scopes(token: &UserToken) -> Result<()> {
+    require_scopes(token, CHAT_SCOPES)
+}
+
+fn require_scopes(token: &UserToken, scopes: &[Scope]) -> Result<()> {
+    let missing: Vec<&str> = scopes
+        .iter()
+        .filter(|scope| !token.scopes().contains(scope))
+        .map(|scope| scope.as_str())
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(Error::MissingScope {
+            login: Some(token.login.to_string()),
+            scopes: missing.join(", "),
+        });
+    }
+
+    Ok(())
+}
+
+/// Refreshes `token` if it has expired or is about to, storing the new token for later runs
+/// at `path`, or where `login` stores it without one
+///
+/// Tokens that were not obtained through `login` have no refresh token and can't be refreshed.
+pub async fn refresh_if_expired<C: Client>(
+    client: &C,
+    token: &mut UserToken,
+    path: Option<&Path>,
+) -> Result<()> {
+    if token.expires_in() > EXPIRY_MARGIN {
+        return Ok(());
+    }
+
+    let stored = StoredToken {
+        client_id: token.client_id().to_string(),
+        access_token: token.access_token.secret().to_string(),
+        refresh_token: token
+            .refresh_token
+            .as_ref()
+            .map(|refresh_token| refresh_token.secret().to_string()),
+        login: token.login.to_string(),
+        user_id: token.user_id.to_string(),
+    };
+    let refreshed = stored.refresh().await?;
+    *token = refreshed.validate(client).await?;
+    match path {
+        Some(path) => refreshed.save_to(path)?,
+        None => {
+            refreshed.save()?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,478 @@
+use std::io::Write;
+use std::time::Duration;
+
+use console::Term;
+use futures_util::{SinkExt, StreamExt};
+use prediction_creator::validation::DEFAULT_PREDICTION_WINDOW;
+use prediction_creator::{find_outcome, Error, PredictionService, Totals};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::PredictionStatus;
+
+use crate::output::{Output, PredictionDocument};
+use crate::{print_end_prediction, print_prediction};
+
+/// Twitch IRC over WebSocket, overridable with `TWITCH_IRC_URL`
+pub const DEFAULT_IRC_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+
+/// Twitch drops chat messages longer than this
+const MAX_MESSAGE_LENGTH: usize = 500;
+
+const PREDICT_USAGE: &str = "Usage: !predict \"Title\" outcome1 outcome2 [window in seconds]";
+const WINNER_USAGE: &str = "Usage: !winner <outcome number or title>";
+
+/// How long to wait before reconnecting after the connection was lost, doubled after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest to wait between attempts to reconnect
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A command typed in chat
+#[derive(Debug, PartialEq, Eq)]
+enum ChatCommand {
+    Predict {
+        title: String,
+        outcomes: Vec<String>,
+        prediction_window: Option<i64>,
+    },
+    Lock,
+    Winner(String),
+    Cancel,
+}
+
+impl ChatCommand {
+    /// Parses a chat message, returning `None` if it isn't one of our commands
+    /// and the usage if it is one but its arguments are off
+    fn parse(text: &str) -> Option<Result<Self, String>> {
+        // Chat clients append an invisible tag character to repeated messages
+        let text = text.trim_end_matches(['\u{e0000}', ' ']).trim();
+        let (name, rest) = text.split_once(' ').unwrap_or((text, ""));
+
+        let command = match name.to_lowercase().as_str() {
+            "!predict" => parse_predict(rest),
+            "!lock" => Ok(ChatCommand::Lock),
+            "!winner" if rest.trim().is_empty() => Err(WINNER_USAGE.to_string()),
+            "!winner" => Ok(ChatCommand::Winner(rest.trim().to_string())),
+            "!cancel" => Ok(ChatCommand::Cancel),
+            _ => return None,
+        };
+
+        Some(command)
+    }
+}
+
+/// Parses `"Title" outcome1 outcome2 [window]`, where a trailing number is the
+/// prediction window as long as two outcomes remain
+fn parse_predict(args: &str) -> Result<ChatCommand, String> {
+    let mut args = split_args(args).ok_or_else(|| PREDICT_USAGE.to_string())?;
+    if args.len() < 3 {
+        return Err(PREDICT_USAGE.to_string());
+    }
+
+    let prediction_window = match args.last().map(|last| last.parse::<i64>()) {
+        Some(Ok(window)) if args.len() > 3 => {
+            args.pop();
+            Some(window)
+        }
+        _ => None,
+    };
+    let title = args.remove(0);
+
+    Ok(ChatCommand::Predict {
+        title,
+        outcomes: args,
+        prediction_window,
+    })
+}
+
+/// Splits on whitespace, keeping double quoted parts together. `None` if a quote isn't closed
+fn split_args(text: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quoted = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quoted {
+        return None;
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    Some(args)
+}
+
+/// A line received from Twitch IRC, with its IRCv3 tags
+struct IrcMessage<'a> {
+    tags: Vec<(&'a str, &'a str)>,
+    prefix: Option<&'a str>,
+    command: &'a str,
+    params: Vec<&'a str>,
+}
+
+impl<'a> IrcMessage<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut rest = line;
+
+        let mut tags = Vec::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw, after) = tagged.split_once(' ')?;
+            tags = raw
+                .split(';')
+                .map(|tag| tag.split_once('=').unwrap_or((tag, "")))
+                .collect();
+            rest = after;
+        }
+
+        let mut prefix = None;
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (raw, after) = prefixed.split_once(' ')?;
+            prefix = Some(raw);
+            rest = after;
+        }
+
+        let (middle, trailing) = match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut words = middle.split(' ').filter(|word| !word.is_empty());
+        let command = words.next()?;
+        let mut params: Vec<&str> = words.collect();
+        params.extend(trailing);
+
+        Some(Self {
+            tags,
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    fn tag(&self, name: &str) -> Option<&'a str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The display name of whoever sent the message, falling back to their nick
+    fn sender(&self) -> &'a str {
+        self.tag("display-name")
+            .or_else(|| self.prefix.and_then(|prefix| prefix.split('!').next()))
+            .unwrap_or_default()
+    }
+
+    /// Whether the sender is the broadcaster or one of their moderators, going by their badges
+    fn is_privileged(&self) -> bool {
+        self.tag("badges").is_some_and(|badges| {
+            badges
+                .split(',')
+                .filter_map(|badge| badge.split('/').next())
+                .any(|badge| badge == "broadcaster" || badge == "moderator")
+        })
+    }
+}
+
+/// A connection to the broadcaster's chat
+struct Chat {
+    socket: Socket,
+    channel: String,
+}
+
+impl Chat {
+    /// Connects and joins the broadcaster's channel, as the broadcaster
+    async fn connect(url: &str, service: &mut PredictionService) -> anyhow::Result<Self> {
+        // Chat doesn't refresh the token like Helix calls do, and the bot outlives it
+        let token = service.fresh_token().await?;
+        let login = token.login.to_string();
+        let pass = format!("PASS oauth:{}", token.access_token.secret());
+
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .map_err(|e| Error::Network(Box::new(e)))?;
+        let mut chat = Chat {
+            socket,
+            channel: format!("#{login}"),
+        };
+
+        chat.send("CAP REQ :twitch.tv/tags twitch.tv/commands")
+            .await?;
+        chat.send(&pass).await?;
+        chat.send(&format!("NICK {login}")).await?;
+
+        // Twitch answers with 001 once the token was accepted
+        loop {
+            let Some(text) = chat.receive().await? else {
+                anyhow::bail!("Twitch chat closed the connection while logging in");
+            };
+            for line in text.lines() {
+                let Some(message) = IrcMessage::parse(line) else {
+                    continue;
+                };
+                match message.command {
+                    "001" => {
+                        chat.send(&format!("JOIN {}", chat.channel)).await?;
+                        return Ok(chat);
+                    }
+                    "NOTICE" => {
+                        let notice = message.params.last().copied().unwrap_or_default();
+                        return Err(Error::InvalidToken(format!(
+                            "Twitch chat rejected the token: {notice}"
+                        ))
+                        .into());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    async fn send(&mut self, line: &str) -> anyhow::Result<()> {
+        self.socket.send(Message::text(line)).await?;
+        Ok(())
+    }
+
+    /// The next batch of lines, or `None` once the connection is closed
+    async fn receive(&mut self) -> anyhow::Result<Option<String>> {
+        loop {
+            match self.socket.next().await {
+                Some(Ok(Message::Text(text))) => return Ok(Some(text.to_string())),
+                Some(Ok(Message::Close(_))) | None => return Ok(None),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Replies to the chat message with id `parent`, shortening the reply to fit
+    async fn reply(&mut self, parent: Option<&str>, text: &str) -> anyhow::Result<()> {
+        let text: String = text
+            .chars()
+            .filter(|c| *c != '\r' && *c != '\n')
+            .take(MAX_MESSAGE_LENGTH)
+            .collect();
+        let line = match parent {
+            Some(parent) => format!(
+                "@reply-parent-msg-id={parent} PRIVMSG {} :{text}",
+                self.channel
+            ),
+            None => format!("PRIVMSG {} :{text}", self.channel),
+        };
+        self.send(&line).await
+    }
+}
+
+/// Manages predictions from commands typed in chat by the broadcaster and moderators, until interrupted
+///
+/// Commands from anyone else are ignored. Each command is answered in chat, and the
+/// connection is reestablished whenever it is closed or fails, backing off while Twitch
+/// can't be reached. Only a token Twitch won't take any more stops the bot.
+pub async fn run(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    url: &str,
+) -> anyhow::Result<()> {
+    let mut chat = Chat::connect(url, service).await?;
+    loop {
+        writeln!(
+            term,
+            "Listening for !predict, !lock, !winner and !cancel in {}",
+            console::style(&chat.channel).bold()
+        )?;
+
+        let result = tokio::select! {
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+            result = listen(term, output, service, &mut chat) => result,
+        };
+        match result {
+            Ok(()) => writeln!(term, "{}", console::style("Reconnecting to chat").yellow())?,
+            Err(e) => retry_unless_token_error(term, "Lost the chat connection", e)?,
+        }
+
+        let mut delay = RECONNECT_DELAY;
+        chat = loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+                _ = tokio::time::sleep(delay) => {}
+            }
+            match Chat::connect(url, service).await {
+                Ok(chat) => break chat,
+                Err(e) => retry_unless_token_error(term, "Unable to reconnect to chat", e)?,
+            }
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        };
+    }
+}
+
+/// Logs a failed chat connection so it can be retried, or returns the error if the token
+/// has to be replaced before retrying can help
+fn retry_unless_token_error(
+    term: &mut Term,
+    context: &str,
+    error: anyhow::Error,
+) -> anyhow::Result<()> {
+    if error
+        .downcast_ref::<Error>()
+        .is_some_and(Error::is_token_error)
+    {
+        return Err(error);
+    }
+
+    writeln!(
+        term,
+        "{} {error:#}",
+        console::style(format!("{context}, retrying:")).yellow()
+    )?;
+
+    Ok(())
+}
+
+/// Answers the commands in chat until Twitch closes the connection or asks us to reconnect
+async fn listen(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    chat: &mut Chat,
+) -> anyhow::Result<()> {
+    while let Some(text) = chat.receive().await? {
+        for line in text.lines() {
+            let Some(message) = IrcMessage::parse(line) else {
+                continue;
+            };
+            match message.command {
+                "PING" => {
+                    let server = message.params.last().copied().unwrap_or("tmi.twitch.tv");
+                    chat.send(&format!("PONG :{server}")).await?;
+                }
+                "RECONNECT" => return Ok(()),
+                "PRIVMSG" => {
+                    let text = message.params.last().copied().unwrap_or_default();
+                    let Some(command) = ChatCommand::parse(text) else {
+                        continue;
+                    };
+                    if !message.is_privileged() {
+                        continue;
+                    }
+
+                    writeln!(term, "{}: {text}", console::style(message.sender()).bold())?;
+                    let reply = match command {
+                        Ok(command) => match execute(term, output, service, command).await {
+                            Ok(reply) => reply,
+                            Err(e) => {
+                                writeln!(term, "{} {e}", console::style("Failed:").red())?;
+                                e.to_string()
+                            }
+                        },
+                        Err(usage) => usage,
+                    };
+                    chat.reply(message.tag("id"), &reply).await?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a command from chat, returning the reply
+async fn execute(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    command: ChatCommand,
+) -> anyhow::Result<String> {
+    match command {
+        ChatCommand::Predict {
+            title,
+            outcomes,
+            prediction_window,
+        } => {
+            let prediction_window = prediction_window.unwrap_or(DEFAULT_PREDICTION_WINDOW);
+            let prediction =
+                output.journaled(service.create(&title, &outcomes, prediction_window).await?);
+            print_prediction(term, &prediction)?;
+            output.emit(&PredictionDocument::new("created", Some(&prediction)));
+
+            Ok(format!(
+                "Prediction \"{}\" started, open for {}s: {}",
+                prediction.title,
+                prediction.prediction_window,
+                numbered_outcomes(&prediction)
+            ))
+        }
+        ChatCommand::Lock => {
+            let prediction = service.require_current().await?;
+            if prediction.status == PredictionStatus::Locked {
+                return Err(Error::AlreadyLocked(prediction.title).into());
+            }
+            let locked = output.journaled(service.lock(&prediction.id).await?);
+            print_end_prediction(term, &locked, "locked")?;
+            output.emit(&PredictionDocument::new("locked", Some(&locked)));
+
+            let totals = Totals::of(&locked);
+            Ok(format!(
+                "Prediction \"{}\" locked with {} channel points from {} users",
+                locked.title, totals.channel_points, totals.users
+            ))
+        }
+        ChatCommand::Winner(winner) => {
+            let prediction = service.require_current().await?;
+            let outcome = find_outcome(&prediction.outcomes, &winner)?;
+            let ended = output.journaled(service.resolve(&prediction.id, &outcome.id).await?);
+            print_end_prediction(term, &ended, "resolved")?;
+            output.emit(&PredictionDocument::new("resolved", Some(&ended)));
+
+            Ok(format!(
+                "Prediction \"{}\" resolved, {} won",
+                ended.title, outcome.title
+            ))
+        }
+        ChatCommand::Cancel => {
+            let prediction = service.require_current().await?;
+            let ended = output.journaled(service.cancel(&prediction.id).await?);
+            print_end_prediction(term, &ended, "canceled")?;
+            output.emit(&PredictionDocument::new("canceled", Some(&ended)));
+
+            Ok(format!(
+                "Prediction \"{}\" canceled, all channel points were refunded",
+                ended.title
+            ))
+        }
+    }
+}
+
+fn numbered_outcomes(prediction: &Prediction) -> String {
+    prediction
+        .outcomes
+        .iter()
+        .enumerate()
+        .map(|(i, outcome)| format!("[{}] {}", i + 1, outcome.title))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
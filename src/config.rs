@@ -0,0 +1,53 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::{Error, Result};
+
+const APP_DIR: &str = "prediction-creator";
+
+/// Returns the directory holding our configuration, following the XDG base directory spec
+///
+/// This is `$XDG_CONFIG_HOME/prediction-creator`, falling back to `~/.config/prediction-creator`,
+/// and to `%APPDATA%\prediction-creator` on Windows, where `HOME` usually isn't set.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(config_home) = var("XDG_CONFIG_HOME") {
+        return Ok(PathBuf::from(config_home).join(APP_DIR));
+    }
+    if let Some(home) = var("HOME") {
+        return Ok(PathBuf::from(home).join(".config").join(APP_DIR));
+    }
+
+    let app_data = var("APPDATA").ok_or_else(|| {
+        Error::Storage("None of XDG_CONFIG_HOME, HOME or APPDATA is set".to_string())
+    })?;
+
+    Ok(PathBuf::from(app_data).join(APP_DIR))
+}
+
+/// Returns the directory holding state kept between runs, following the XDG base directory spec
+///
+/// This is `$XDG_STATE_HOME/prediction-creator`, falling back to `~/.local/state/prediction-creator`,
+/// and to `%LOCALAPPDATA%\prediction-creator` on Windows.
+pub fn state_dir() -> Result<PathBuf> {
+    if let Some(state_home) = var("XDG_STATE_HOME") {
+        return Ok(PathBuf::from(state_home).join(APP_DIR));
+    }
+    if let Some(home) = var("HOME") {
+        return Ok(PathBuf::from(home)
+            .join(".local")
+            .join("state")
+            .join(APP_DIR));
+    }
+
+    let local_app_data = var("LOCALAPPDATA").ok_or_else(|| {
+        Error::Storage("None of XDG_STATE_HOME, HOME or LOCALAPPDATA is set".to_string())
+    })?;
+
+    Ok(PathBuf::from(local_app_data).join(APP_DIR))
+}
+
+/// The environment variable `name`, treating an empty one as unset
+fn var(name: &str) -> Option<OsString> {
+    env::var_os(name).filter(|value| !value.is_empty())
+}
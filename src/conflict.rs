@@ -0,0 +1,132 @@
+use std::io::Write;
+
+use console::{pad_str, Alignment, Term};
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Select;
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::PredictionOutcome;
+
+/// What `create` does when another prediction is already active
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnActive {
+    /// Keep the active prediction and don't start the requested one
+    Adopt,
+    /// Fail without touching the active prediction
+    Fail,
+    /// Cancel the active prediction, refunding all channel points, then start the requested one
+    CancelAndReplace,
+    /// Resolve the active prediction, then start the requested one
+    ResolveThenReplace,
+}
+
+/// Shows the active and the requested prediction side by side and asks what to do
+pub fn ask(
+    term: &mut Term,
+    active: &Prediction,
+    title: &str,
+    outcomes: &[String],
+    prediction_window: i64,
+) -> anyhow::Result<OnActive> {
+    writeln!(
+        term,
+        "{}",
+        console::style("Another prediction is already active").yellow()
+    )?;
+    print_side_by_side(term, active, title, outcomes, prediction_window)?;
+
+    let choices = [
+        ("Keep the active prediction", OnActive::Adopt),
+        (
+            "Cancel the active prediction and start the new one",
+            OnActive::CancelAndReplace,
+        ),
+        (
+            "Resolve the active prediction and start the new one",
+            OnActive::ResolveThenReplace,
+        ),
+        ("Abort", OnActive::Fail),
+    ];
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("what should happen to the active prediction")
+        .default(0)
+        .items(&choices.map(|(label, _)| label))
+        .interact()?;
+
+    Ok(choices[selection].1)
+}
+
+/// Prompts for the winning outcome of the active prediction
+pub fn ask_winner(outcomes: &[PredictionOutcome]) -> anyhow::Result<&PredictionOutcome> {
+    let items: Vec<String> = outcomes
+        .iter()
+        .enumerate()
+        .map(|(i, outcome)| format!("[{}] {}", i + 1, outcome.title))
+        .collect();
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("which outcome of the active prediction won")
+        .default(0)
+        .items(&items)
+        .interact()?;
+
+    Ok(&outcomes[selection])
+}
+
+fn print_side_by_side(
+    term: &mut Term,
+    active: &Prediction,
+    title: &str,
+    outcomes: &[String],
+    prediction_window: i64,
+) -> anyhow::Result<()> {
+    let mut left = vec![active.title.clone()];
+    left.extend(
+        active
+            .outcomes
+            .iter()
+            .enumerate()
+            .map(|(i, outcome)| format!("  [{}] {}", i + 1, outcome.title)),
+    );
+    left.push(format!(
+        "{:?}, {}s window",
+        active.status, active.prediction_window
+    ));
+
+    let mut right = vec![title.to_string()];
+    right.extend(
+        outcomes
+            .iter()
+            .enumerate()
+            .map(|(i, outcome)| format!("  [{}] {}", i + 1, outcome)),
+    );
+    right.push(format!("New, {prediction_window}s window"));
+
+    let width = left
+        .iter()
+        .map(|line| console::measure_text_width(line))
+        .chain([console::measure_text_width(ACTIVE_HEADER)])
+        .max()
+        .unwrap_or_default();
+
+    writeln!(
+        term,
+        "{}   {}",
+        console::style(pad_str(ACTIVE_HEADER, width, Alignment::Left, None)).bold(),
+        console::style(REQUESTED_HEADER).bold()
+    )?;
+    for i in 0..left.len().max(right.len()) {
+        let left = left.get(i).map(String::as_str).unwrap_or_default();
+        let right = right.get(i).map(String::as_str).unwrap_or_default();
+        writeln!(
+            term,
+            "{}   {}",
+            pad_str(left, width, Alignment::Left, None),
+            right
+        )?;
+    }
+
+    Ok(())
+}
+
+const ACTIVE_HEADER: &str = "Active";
+const REQUESTED_HEADER: &str = "Requested";
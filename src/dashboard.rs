@@ -0,0 +1,355 @@
+use std::io::Write;
+use std::sync::mpsc as std_mpsc;
+use std::time::Duration;
+
+use console::{Key, Term};
+use prediction_creator::eventsub::PredictionEvents;
+use prediction_creator::{Error, PredictionService, Result, Totals};
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::PredictionStatus;
+
+use crate::{events, print_end_prediction};
+
+/// Width of the percentage bar, in characters
+const BAR_WIDTH: usize = 30;
+
+/// An action picked with a keyboard shortcut, waiting to be confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shortcut {
+    Resolve(usize),
+    Lock,
+    Cancel,
+}
+
+/// Why the dashboard was closed
+enum Exit {
+    Quit,
+    /// Ended with the verb to summarize it with, and why it couldn't be journaled if it wasn't
+    Ended(&'static str, Option<Error>),
+    EndedElsewhere,
+}
+
+/// Reads keys on a separate thread, one key per request
+///
+/// Keys are only read when asked for, so the terminal is never left in raw mode
+/// by a read that is still pending when we exit.
+struct KeyReader {
+    requests: std_mpsc::Sender<()>,
+    keys: mpsc::Receiver<std::io::Result<Key>>,
+    /// Whether a key was asked for that wasn't received yet
+    pending: bool,
+}
+
+impl KeyReader {
+    fn spawn(term: Term) -> Self {
+        let (requests, request_rx) = std_mpsc::channel::<()>();
+        let (key_tx, keys) = mpsc::channel(1);
+
+        std::thread::spawn(move || {
+            for () in request_rx {
+                if key_tx.blocking_send(term.read_key()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests,
+            keys,
+            pending: false,
+        }
+    }
+
+    fn request(&mut self) {
+        // The thread only goes away once we drop the receiving end
+        let _ = self.requests.send(());
+        self.pending = true;
+    }
+
+    async fn next(&mut self) -> Option<std::io::Result<Key>> {
+        let key = self.keys.recv().await;
+        self.pending = false;
+        key
+    }
+}
+
+/// Shows a live view of the prediction, redrawn every `interval`
+///
+/// The prediction is kept up to date by `events`, or by polling Twitch every `interval`
+/// without them. Returns the prediction as it was when the dashboard was closed.
+///
+/// Outcomes can be resolved with their number, the prediction locked with `l`
+/// and canceled with `c`, each confirmed with `y`.
+pub async fn run(
+    term: &mut Term,
+    service: &mut PredictionService,
+    prediction: Prediction,
+    events: Option<PredictionEvents>,
+    interval: Duration,
+) -> anyhow::Result<Prediction> {
+    let mut prediction = prediction;
+    let mut keys = KeyReader::spawn(term.clone());
+    keys.request();
+
+    term.hide_cursor()?;
+    let result = watch(term, service, &mut prediction, &mut keys, events, interval).await;
+    term.show_cursor()?;
+
+    let exit = match result {
+        Ok(exit) => exit,
+        Err(e) => {
+            // A key read may still be pending, wait for it so the terminal is restored
+            if keys.pending {
+                writeln!(
+                    term,
+                    "{} {e:#}, press any key to exit",
+                    console::style("Error:").red().bold()
+                )?;
+                let _ = keys.next().await;
+            }
+            return Err(e);
+        }
+    };
+
+    match exit {
+        Exit::EndedElsewhere => {
+            term.clear_screen()?;
+            print_end_prediction(term, &prediction, "ended")?;
+            writeln!(
+                term,
+                "The prediction was ended elsewhere, press any key to exit"
+            )?;
+            // A key read is still pending, wait for it so the terminal is restored
+            let _ = keys.next().await;
+        }
+        Exit::Ended(verb, journal_error) => {
+            term.clear_screen()?;
+            print_end_prediction(term, &prediction, verb)?;
+            if let Some(e) = journal_error {
+                writeln!(term, "{} {e}", console::style("Warning:").yellow().bold())?;
+            }
+        }
+        Exit::Quit => {}
+    }
+
+    Ok(prediction)
+}
+
+/// Redraws the prediction until it ends or the dashboard is closed
+async fn watch(
+    term: &mut Term,
+    service: &mut PredictionService,
+    prediction: &mut Prediction,
+    keys: &mut KeyReader,
+    mut events: Option<PredictionEvents>,
+    interval: Duration,
+) -> anyhow::Result<Exit> {
+    let mut pending = None;
+    let mut error = None;
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                // Events keep the prediction up to date, the ticker then only moves the countdown along
+                if events.is_none() {
+                    if let Some(latest) = recover(service.get(&prediction.id).await, &mut error)? {
+                        *prediction = latest;
+                    }
+                }
+            }
+            event = events::next(&mut events) => match event {
+                Ok(event) => event.apply(prediction),
+                // The screen is redrawn right away, so there is no point in a warning
+                Err(_) => {
+                    events = None;
+                    if let Some(latest) = recover(service.get(&prediction.id).await, &mut error)? {
+                        *prediction = latest;
+                    }
+                }
+            },
+            key = keys.next() => {
+                let key = match key {
+                    Some(Ok(key)) => key,
+                    Some(Err(e)) if e.kind() == std::io::ErrorKind::Interrupted => return Ok(Exit::Quit),
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Ok(Exit::Quit),
+                };
+
+                match (key, pending) {
+                    (Key::Char('q') | Key::Escape, _) => return Ok(Exit::Quit),
+                    (Key::Char('y'), Some(action)) => {
+                        pending = None;
+                        let (ended, verb) = match action {
+                            Shortcut::Resolve(i) => {
+                                let winner = prediction.outcomes[i].id.clone();
+                                (service.resolve(&prediction.id, &winner).await, "resolved")
+                            }
+                            Shortcut::Lock => (service.lock(&prediction.id).await, "locked"),
+                            Shortcut::Cancel => (service.cancel(&prediction.id).await, "canceled"),
+                        };
+                        if let Some(ended) = recover(ended, &mut error)? {
+                            *prediction = ended.prediction;
+                            if action != Shortcut::Lock {
+                                return Ok(Exit::Ended(verb, ended.journal_error));
+                            }
+                            error = ended.journal_error.map(|e| e.to_string());
+                        }
+                    }
+                    (Key::Char('l'), _) if prediction.status == PredictionStatus::Active => {
+                        pending = Some(Shortcut::Lock)
+                    }
+                    (Key::Char('c'), _) => pending = Some(Shortcut::Cancel),
+                    (Key::Char(c), _) => {
+                        pending = c
+                            .to_digit(10)
+                            .and_then(|n| (n as usize).checked_sub(1))
+                            .filter(|&i| i < prediction.outcomes.len())
+                            .map(Shortcut::Resolve);
+                    }
+                    _ => pending = None,
+                }
+                keys.request();
+            }
+        }
+
+        if matches!(
+            prediction.status,
+            PredictionStatus::Resolved | PredictionStatus::Canceled
+        ) {
+            return Ok(Exit::EndedElsewhere);
+        }
+
+        term.clear_screen()?;
+        render(
+            term,
+            prediction,
+            pending,
+            error.as_deref(),
+            OffsetDateTime::now_utc(),
+        )?;
+    }
+}
+
+/// Keeps a failed request on screen instead of closing the dashboard, as the next poll or
+/// key press may well succeed. Only a token Twitch won't take any more is returned as an error.
+fn recover<T>(result: Result<T>, error: &mut Option<String>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => {
+            *error = None;
+            Ok(Some(value))
+        }
+        Err(e) if e.is_token_error() => Err(e),
+        Err(e) => {
+            *error = Some(e.to_string());
+            Ok(None)
+        }
+    }
+}
+
+fn format_duration(seconds: i64) -> String {
+    if seconds >= 60 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+fn render(
+    term: &mut Term,
+    prediction: &Prediction,
+    pending: Option<Shortcut>,
+    error: Option<&str>,
+    now: OffsetDateTime,
+) -> anyhow::Result<()> {
+    writeln!(term, "{}", console::style(&prediction.title).bold())?;
+
+    match prediction.status {
+        PredictionStatus::Active => {
+            let locks_at = prediction.created_at.to_utc()
+                + time::Duration::seconds(prediction.prediction_window);
+            let remaining = (locks_at - now).whole_seconds().max(0);
+            writeln!(term, "Locks in {}", format_duration(remaining))?;
+        }
+        PredictionStatus::Locked => writeln!(term, "{}", console::style("Locked").yellow())?,
+        ref status => writeln!(term, "{status:?}")?,
+    }
+    writeln!(term)?;
+
+    let Totals {
+        channel_points: total_points,
+        users: total_users,
+    } = Totals::of(prediction);
+
+    for (i, outcome) in prediction.outcomes.iter().enumerate() {
+        let points = outcome.channel_points.unwrap_or_default();
+        let share = if total_points > 0 {
+            points as f64 / total_points as f64
+        } else {
+            0.0
+        };
+        let odds = if points > 0 {
+            format!("1:{:.2}", total_points as f64 / points as f64)
+        } else {
+            "-".to_string()
+        };
+        let filled = (share * BAR_WIDTH as f64).round() as usize;
+        let bar = format!("{}{}", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled));
+        // Helix spells the colors in uppercase, EventSub in lowercase
+        let bar = if outcome.color.eq_ignore_ascii_case("pink") {
+            console::style(bar).magenta()
+        } else {
+            console::style(bar).blue()
+        };
+
+        writeln!(
+            term,
+            "[{}] {}",
+            i + 1,
+            console::style(&outcome.title).bold()
+        )?;
+        writeln!(
+            term,
+            "    {bar} {:>5.1}%  {} points, {} users, odds {odds}",
+            share * 100.0,
+            points,
+            outcome.users.unwrap_or_default()
+        )?;
+    }
+
+    writeln!(term)?;
+    writeln!(
+        term,
+        "{total_points} channel points from {total_users} users"
+    )?;
+    writeln!(term)?;
+
+    if let Some(error) = error {
+        writeln!(term, "{} {error}", console::style("Error:").red().bold())?;
+        writeln!(term)?;
+    }
+
+    match pending {
+        Some(Shortcut::Resolve(i)) => writeln!(
+            term,
+            "Resolve with {}? Press y to confirm",
+            console::style(&prediction.outcomes[i].title).bold()
+        )?,
+        Some(Shortcut::Lock) => writeln!(term, "Lock the prediction? Press y to confirm")?,
+        Some(Shortcut::Cancel) => writeln!(term, "Cancel the prediction? Press y to confirm")?,
+        None if prediction.status == PredictionStatus::Active => writeln!(
+            term,
+            "[1-{}] resolve  [l] lock  [c] cancel  [q] quit",
+            prediction.outcomes.len()
+        )?,
+        None => writeln!(
+            term,
+            "[1-{}] resolve  [c] cancel  [q] quit",
+            prediction.outcomes.len()
+        )?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use serde::Serialize;
+use twitch_api::helix::predictions::{create_prediction, end_prediction};
+use twitch_api::helix::Request;
+use twitch_api::types::{PredictionIdRef, PredictionStatus, UserId};
+
+use crate::service::{end_body, new_outcomes};
+use crate::{validation, Result};
+
+/// A Helix request built exactly like it would be sent, but not sent
+#[derive(Debug, Serialize)]
+pub struct PlannedRequest {
+    pub method: &'static str,
+    pub endpoint: String,
+    pub body: serde_json::Value,
+}
+
+/// The request [`PredictionService::create`](crate::PredictionService::create) would send
+pub fn create(
+    broadcaster_id: &UserId,
+    title: &str,
+    outcomes: &[String],
+    prediction_window: i64,
+) -> Result<PlannedRequest> {
+    validation::validate_prediction(title, outcomes, prediction_window)?;
+
+    let outcomes = new_outcomes(outcomes);
+    let body = create_prediction::CreatePredictionBody::new(
+        broadcaster_id,
+        title,
+        &outcomes,
+        prediction_window,
+    );
+
+    Ok(PlannedRequest {
+        method: "POST",
+        endpoint: endpoint::<create_prediction::CreatePredictionRequest>(),
+        body: serde_json::to_value(body).expect("the body to serialize"),
+    })
+}
+
+/// The request that locks, resolves or cancels the prediction, depending on `new_status`
+pub fn end(
+    broadcaster_id: &UserId,
+    prediction_id: &PredictionIdRef,
+    new_status: PredictionStatus,
+    winning_outcome_id: Option<String>,
+) -> PlannedRequest {
+    let body = end_body(
+        broadcaster_id,
+        prediction_id,
+        new_status,
+        winning_outcome_id,
+    );
+
+    PlannedRequest {
+        method: "PATCH",
+        endpoint: endpoint::<end_prediction::EndPredictionRequest>(),
+        body: serde_json::to_value(body).expect("the body to serialize"),
+    }
+}
+
+/// The URL of the endpoint, under the Helix root configured in `TWITCH_HELIX_URL`
+fn endpoint<R: Request>() -> String {
+    R::get_bare_uri().map_or_else(|_| R::PATH.to_string(), |uri| uri.to_string())
+}
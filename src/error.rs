@@ -0,0 +1,211 @@
+use twitch_api::helix::{
+    ClientRequestError, HelixRequestGetError, HelixRequestPatchError, HelixRequestPostError,
+};
+use twitch_api::twitch_oauth2::tokens::errors::ValidationError;
+use twitch_api::types::PredictionId;
+
+use crate::validation::ValidationErrors;
+
+/// Everything that can go wrong while managing predictions
+///
+/// Each variant maps to a process exit code through [`Error::exit_code`], so scripts
+/// can tell failures apart without parsing messages:
+///
+/// | Code | Meaning                                             |
+/// |------|-----------------------------------------------------|
+/// | 1    | Any other error                                     |
+/// | 2    | The prediction or the arguments are invalid         |
+/// | 3    | No token was found                                  |
+/// | 4    | The token is invalid or has expired                 |
+/// | 5    | The token is missing a required scope               |
+/// | 6    | A prediction is already active                      |
+/// | 7    | The channel is not a Twitch partner or affiliate    |
+/// | 8    | Twitch is rate limiting us                          |
+/// | 9    | Twitch could not be reached                         |
+/// | 10   | There is no active prediction to act on             |
+/// | 11   | The token can't manage the requested channel        |
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Invalid(#[from] ValidationErrors),
+    #[error("{0}")]
+    InvalidArguments(String),
+    #[error("Prediction {position} in {path} is invalid")]
+    InvalidQueued {
+        position: usize,
+        path: String,
+        #[source]
+        errors: ValidationErrors,
+    },
+    #[error("No access token found, set TWITCH_ACCESS_TOKEN or run `prediction-creator login`")]
+    NoToken,
+    #[error("No token found for profile {0:?}, run `prediction-creator login --profile {0}`")]
+    NoProfileToken(String),
+    #[error("No profile named {name:?} in {path}, add it with `prediction-creator login --profile {name}`. Available profiles are: {available}")]
+    UnknownProfile {
+        name: String,
+        path: String,
+        available: String,
+    },
+    #[error("{0}, run `prediction-creator login` to get a new token")]
+    InvalidToken(String),
+    #[error("The token{} is missing the required scopes: {scopes}. Run `prediction-creator login` to get a new token", .login.as_ref().map(|login| format!(" for {login}")).unwrap_or_default())]
+    MissingScope {
+        /// Who the token is for, unless Twitch rejected it without saying
+        login: Option<String>,
+        scopes: String,
+    },
+    #[error("A prediction is already active: {0}")]
+    PredictionAlreadyActive(String),
+    #[error("Predictions are only available to Twitch partners and affiliates: {0}")]
+    NotAffiliate(String),
+    #[error("Twitch is rate limiting requests, try again in a minute")]
+    RateLimited,
+    #[error("Unable to reach Twitch: {0}")]
+    Network(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("There is no active prediction")]
+    NoActivePrediction,
+    #[error("Prediction {0} was not found")]
+    PredictionNotFound(PredictionId),
+    #[error("Prediction {0} is already locked")]
+    AlreadyLocked(String),
+    #[error("Winner {winner:?} does not match any outcome, available outcomes are: {available}")]
+    UnknownOutcome { winner: String, available: String },
+    #[error("Winner {winner:?} is ambiguous: {reason}")]
+    AmbiguousOutcome { winner: String, reason: String },
+    #[error(
+        "Twitch rejected the request to end the prediction as invalid, it may already have ended"
+    )]
+    BadRequest,
+    #[error("Unexpected response while ending the prediction: {0}")]
+    UnexpectedResponse(String),
+    #[error("Login failed: {0}")]
+    Login(String),
+    #[error("{0}")]
+    Storage(String),
+    #[error("{0}")]
+    Queue(String),
+    #[error("Prediction events stopped: {0}")]
+    EventSub(String),
+    #[error("There is no Twitch channel named {0:?}")]
+    UnknownChannel(String),
+    #[error("The token belongs to {login}, but Twitch only lets broadcasters manage their own predictions. To manage {channel}'s predictions, {channel} must run `prediction-creator login` and hand you that token, e.g. to store as a `--profile`")]
+    WrongChannel { channel: String, login: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Helix(Box<ClientRequestError<reqwest::Error>>),
+}
+
+impl Error {
+    /// The process exit code for this error, see the table on [`Error`]
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Invalid(_)
+            | Error::InvalidArguments(_)
+            | Error::InvalidQueued { .. }
+            | Error::UnknownProfile { .. }
+            | Error::UnknownChannel(_) => 2,
+            Error::NoToken | Error::NoProfileToken(_) => 3,
+            Error::InvalidToken(_) => 4,
+            Error::MissingScope { .. } => 5,
+            Error::PredictionAlreadyActive(_) => 6,
+            Error::NotAffiliate(_) => 7,
+            Error::RateLimited => 8,
+            Error::Network(_) => 9,
+            Error::NoActivePrediction => 10,
+            Error::WrongChannel { .. } => 11,
+            _ => 1,
+        }
+    }
+
+    /// Whether the token has to be replaced before trying again can help
+    pub fn is_token_error(&self) -> bool {
+        matches!(
+            self,
+            Error::NoToken
+                | Error::NoProfileToken(_)
+                | Error::InvalidToken(_)
+                | Error::MissingScope { .. }
+        )
+    }
+}
+
+/// The status code and message of an error response from Helix, if we got that far
+fn response_error(error: &ClientRequestError<reqwest::Error>) -> Option<(u16, &str)> {
+    match error {
+        ClientRequestError::HelixRequestGetError(HelixRequestGetError::Error {
+            status,
+            message,
+            ..
+        })
+        | ClientRequestError::HelixRequestPostError(HelixRequestPostError::Error {
+            status,
+            message,
+            ..
+        })
+        | ClientRequestError::HelixRequestPatchError(HelixRequestPatchError::Error {
+            status,
+            message,
+            ..
+        }) => Some((status.as_u16(), message)),
+        // Ending a prediction only has a typed response for the statuses Twitch documents
+        ClientRequestError::HelixRequestPatchError(HelixRequestPatchError::InvalidResponse {
+            status,
+            response,
+            ..
+        }) => Some((status.as_u16(), response)),
+        _ => None,
+    }
+}
+
+impl From<ClientRequestError<reqwest::Error>> for Error {
+    fn from(error: ClientRequestError<reqwest::Error>) -> Self {
+        if let ClientRequestError::RequestError(e) = error {
+            return Self::Network(Box::new(e));
+        }
+
+        let Some((status, message)) = response_error(&error) else {
+            return Self::Helix(Box::new(error));
+        };
+        let lowercase = message.to_lowercase();
+
+        match status {
+            _ if lowercase.contains("partner") || lowercase.contains("affiliate") => {
+                Self::NotAffiliate(message.to_string())
+            }
+            400 if matches!(error, ClientRequestError::HelixRequestPostError(_))
+                && lowercase.contains("already") =>
+            {
+                Self::PredictionAlreadyActive(message.to_string())
+            }
+            // Helix answers "Missing scope: channel:manage:predictions"
+            401 if lowercase.starts_with("missing scope") => Self::MissingScope {
+                login: None,
+                scopes: message
+                    .split_once(':')
+                    .map_or(message, |(_, scopes)| scopes)
+                    .trim()
+                    .to_string(),
+            },
+            401 => Self::InvalidToken(format!("Twitch rejected the token: {message}")),
+            403 => Self::NotAffiliate(message.to_string()),
+            429 => Self::RateLimited,
+            _ => Self::Helix(Box::new(error)),
+        }
+    }
+}
+
+impl<RE: std::error::Error + Send + Sync + 'static> From<ValidationError<RE>> for Error {
+    fn from(error: ValidationError<RE>) -> Self {
+        match error {
+            ValidationError::Request(e) => Self::Network(Box::new(e)),
+            ValidationError::NotAuthorized => {
+                Self::InvalidToken("The token is invalid or has expired".to_string())
+            }
+            e => Self::InvalidToken(format!("The token could not be validated: {e}")),
+        }
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
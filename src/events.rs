@@ -0,0 +1,90 @@
+use std::io::Write;
+
+use console::Term;
+use prediction_creator::eventsub::{PredictionEvent, PredictionEvents};
+use prediction_creator::{Error, PredictionService};
+use tokio::sync::oneshot;
+use twitch_api::helix::predictions::Prediction;
+
+/// Subscribes to the channel's prediction events, carrying on without them if that fails
+pub async fn subscribe(
+    term: &mut Term,
+    service: &mut PredictionService,
+    url: &str,
+) -> anyhow::Result<Option<PredictionEvents>> {
+    match service.subscribe(url).await {
+        Ok(events) => Ok(Some(events)),
+        Err(e) => {
+            warn(term, &e)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Waits for the next event, or forever if there are no events to wait for
+///
+/// Meant for `tokio::select!`, where the caller drops the events after an error.
+pub async fn next(
+    events: &mut Option<PredictionEvents>,
+) -> prediction_creator::Result<PredictionEvent> {
+    let Some(events) = events else {
+        return std::future::pending().await;
+    };
+
+    events
+        .next()
+        .await
+        .unwrap_or_else(|| Err(Error::EventSub("the connection was closed".to_string())))
+}
+
+/// Shows a blocking `prompt` while following the prediction's events
+///
+/// Returns `None` if the prediction was resolved or canceled elsewhere in the meantime,
+/// with `prediction` updated to how it ended.
+pub async fn prompt<T: Send + 'static>(
+    term: &mut Term,
+    prediction: &mut Prediction,
+    events: &mut Option<PredictionEvents>,
+    prompt: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<Option<T>> {
+    let (tx, mut selection) = oneshot::channel();
+    // The prompt blocks, so it gets a thread of its own
+    std::thread::spawn(move || {
+        let _ = tx.send(prompt());
+    });
+
+    loop {
+        tokio::select! {
+            selected = &mut selection => return Ok(Some(selected??)),
+            event = next(events) => match event {
+                Ok(event) => {
+                    event.apply(prediction);
+                    if event.ends(prediction) {
+                        writeln!(term)?;
+                        writeln!(
+                            term,
+                            "{}",
+                            console::style("The prediction was ended elsewhere, press enter to continue").yellow()
+                        )?;
+                        // Let the prompt finish, so it restores the terminal
+                        let _ = selection.await;
+                        return Ok(None);
+                    }
+                }
+                Err(e) => {
+                    warn(term, &e)?;
+                    *events = None;
+                }
+            },
+        }
+    }
+}
+
+/// Reports that changes made elsewhere won't be noticed right away
+pub fn warn(term: &mut Term, error: &Error) -> std::io::Result<()> {
+    writeln!(
+        term,
+        "{} {error}",
+        console::style("Not following prediction events:").yellow()
+    )
+}
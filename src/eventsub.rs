@@ -0,0 +1,300 @@
+//! Prediction events pushed over an EventSub WebSocket, so changes made elsewhere,
+//! e.g. Twitch locking the prediction or a moderator resolving it, are seen right away.
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use twitch_api::eventsub::channel::{
+    ChannelPredictionBeginV1, ChannelPredictionEndV1, ChannelPredictionLockV1,
+    ChannelPredictionProgressV1,
+};
+use twitch_api::eventsub::Transport;
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::helix::HelixClient;
+use twitch_api::twitch_oauth2::UserToken;
+use twitch_api::types::{
+    PredictionId, PredictionOutcome, PredictionOutcomeId, PredictionStatus, Timestamp, UserId,
+};
+
+use crate::{Error, Result};
+
+/// The Twitch EventSub WebSocket, overridable with `TWITCH_EVENTSUB_URL`
+pub const DEFAULT_EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+
+/// How long to wait for the welcome message after connecting
+const WELCOME_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Added to the keepalive timeout Twitch announces before giving up on the connection
+const KEEPALIVE_MARGIN: Duration = Duration::from_secs(5);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Which of the prediction subscriptions an event came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PredictionEventKind {
+    Begin,
+    Progress,
+    Lock,
+    End,
+}
+
+/// A change to a prediction
+///
+/// The payloads of all four prediction subscriptions are read into this one shape.
+/// We don't use the payload types of `twitch_api`, as `channel.prediction.end`
+/// has no winning outcome when the prediction was canceled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PredictionEvent {
+    #[serde(skip_deserializing, default = "default_kind")]
+    pub kind: PredictionEventKind,
+    pub id: PredictionId,
+    pub title: String,
+    pub outcomes: Vec<PredictionOutcome>,
+    #[serde(default)]
+    pub status: Option<PredictionStatus>,
+    #[serde(default)]
+    pub winning_outcome_id: Option<PredictionOutcomeId>,
+    #[serde(default)]
+    pub locked_at: Option<Timestamp>,
+    #[serde(default)]
+    pub ended_at: Option<Timestamp>,
+}
+
+fn default_kind() -> PredictionEventKind {
+    PredictionEventKind::Progress
+}
+
+impl PredictionEvent {
+    /// Whether this event is `prediction` being resolved or canceled
+    pub fn ends(&self, prediction: &Prediction) -> bool {
+        self.kind == PredictionEventKind::End && self.id == prediction.id
+    }
+
+    /// Brings `prediction` up to date with this event, if it is about that prediction
+    pub fn apply(&self, prediction: &mut Prediction) {
+        if prediction.id != self.id {
+            return;
+        }
+
+        prediction.outcomes = self.outcomes.clone();
+        match self.kind {
+            PredictionEventKind::Begin | PredictionEventKind::Progress => {}
+            PredictionEventKind::Lock => {
+                prediction.status = PredictionStatus::Locked;
+                prediction.locked_at = self.locked_at.clone();
+            }
+            PredictionEventKind::End => {
+                prediction.status = self
+                    .status
+                    .clone()
+                    .unwrap_or(match self.winning_outcome_id {
+                        Some(_) => PredictionStatus::Resolved,
+                        None => PredictionStatus::Canceled,
+                    });
+                prediction.winning_outcome_id = self.winning_outcome_id.clone();
+                prediction.ended_at = self.ended_at.clone();
+            }
+        }
+    }
+}
+
+/// Prediction events for a channel, read from the WebSocket in the background
+pub struct PredictionEvents {
+    events: mpsc::Receiver<Result<PredictionEvent>>,
+}
+
+impl PredictionEvents {
+    /// Connects to the EventSub WebSocket at `url` and subscribes to the predictions of `broadcaster_id`
+    pub async fn subscribe(
+        url: &str,
+        client: &HelixClient<'static, reqwest::Client>,
+        token: &UserToken,
+        broadcaster_id: &UserId,
+    ) -> Result<Self> {
+        let (mut socket, session) = connect(url).await?;
+
+        // Subscriptions must be created before the welcome's keepalive timeout passes
+        let transport = || Transport::websocket(&session.id);
+        let subscribed = async {
+            client
+                .create_eventsub_subscription(
+                    ChannelPredictionBeginV1::broadcaster_user_id(broadcaster_id.clone()),
+                    transport(),
+                    token,
+                )
+                .await?;
+            client
+                .create_eventsub_subscription(
+                    ChannelPredictionProgressV1::broadcaster_user_id(broadcaster_id.clone()),
+                    transport(),
+                    token,
+                )
+                .await?;
+            client
+                .create_eventsub_subscription(
+                    ChannelPredictionLockV1::broadcaster_user_id(broadcaster_id.clone()),
+                    transport(),
+                    token,
+                )
+                .await?;
+            client
+                .create_eventsub_subscription(
+                    ChannelPredictionEndV1::broadcaster_user_id(broadcaster_id.clone()),
+                    transport(),
+                    token,
+                )
+                .await?;
+            Ok::<_, Error>(())
+        };
+        if let Err(e) = subscribed.await {
+            let _ = socket.close(None).await;
+            return Err(e);
+        }
+
+        let (tx, events) = mpsc::channel(16);
+        tokio::spawn(async move {
+            if let Err(e) = read(socket, session.keepalive, &tx).await {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        Ok(Self { events })
+    }
+
+    /// Waits for the next event, returning `None` once the connection is closed
+    pub async fn next(&mut self) -> Option<Result<PredictionEvent>> {
+        self.events.recv().await
+    }
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    metadata: FrameMetadata,
+    #[serde(default)]
+    payload: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct FrameMetadata {
+    message_type: String,
+    #[serde(default)]
+    subscription_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SessionPayload {
+    session: Session,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    id: String,
+    keepalive_timeout_seconds: Option<u64>,
+    reconnect_url: Option<String>,
+}
+
+struct Welcome {
+    id: String,
+    keepalive: Duration,
+}
+
+fn eventsub_error(error: impl std::fmt::Display) -> Error {
+    Error::EventSub(error.to_string())
+}
+
+/// Connects to `url` and waits for the welcome message
+async fn connect(url: &str) -> Result<(Socket, Welcome)> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .map_err(|e| Error::Network(Box::new(e)))?;
+
+    let welcome = tokio::time::timeout(WELCOME_TIMEOUT, async {
+        while let Some(message) = socket.next().await {
+            let Message::Text(text) = message.map_err(eventsub_error)? else {
+                continue;
+            };
+            let frame: Frame = serde_json::from_str(&text).map_err(eventsub_error)?;
+            if frame.metadata.message_type == "session_welcome" {
+                let session: SessionPayload =
+                    serde_json::from_value(frame.payload).map_err(eventsub_error)?;
+                return Ok(Welcome {
+                    id: session.session.id,
+                    keepalive: Duration::from_secs(
+                        session.session.keepalive_timeout_seconds.unwrap_or(10),
+                    ),
+                });
+            }
+        }
+        Err(eventsub_error(
+            "The connection closed before the welcome message",
+        ))
+    })
+    .await
+    .map_err(|_| eventsub_error("No welcome message received"))??;
+
+    Ok((socket, welcome))
+}
+
+/// Forwards prediction notifications until the connection closes or the receiver goes away
+async fn read(
+    mut socket: Socket,
+    mut keepalive: Duration,
+    tx: &mpsc::Sender<Result<PredictionEvent>>,
+) -> Result<()> {
+    loop {
+        let message = tokio::time::timeout(keepalive + KEEPALIVE_MARGIN, socket.next())
+            .await
+            .map_err(|_| eventsub_error("The connection timed out"))?;
+        let text = match message {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(_))) | None => return Ok(()),
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(eventsub_error(e)),
+        };
+
+        let frame: Frame = serde_json::from_str(&text).map_err(eventsub_error)?;
+        match frame.metadata.message_type.as_str() {
+            "notification" => {
+                let kind = match frame.metadata.subscription_type.as_deref() {
+                    Some("channel.prediction.begin") => PredictionEventKind::Begin,
+                    Some("channel.prediction.progress") => PredictionEventKind::Progress,
+                    Some("channel.prediction.lock") => PredictionEventKind::Lock,
+                    Some("channel.prediction.end") => PredictionEventKind::End,
+                    _ => continue,
+                };
+                let mut event: PredictionEvent =
+                    serde_json::from_value(frame.payload["event"].clone())
+                        .map_err(eventsub_error)?;
+                event.kind = kind;
+                if tx.send(Ok(event)).await.is_err() {
+                    let _ = socket.close(None).await;
+                    return Ok(());
+                }
+            }
+            "session_reconnect" => {
+                // Subscriptions carry over to the new connection, which we switch to once it's welcomed us
+                let session: SessionPayload =
+                    serde_json::from_value(frame.payload).map_err(eventsub_error)?;
+                let url = session
+                    .session
+                    .reconnect_url
+                    .ok_or_else(|| eventsub_error("Reconnect message without a URL"))?;
+                let (new_socket, welcome) = connect(&url).await?;
+                let _ = socket.close(None).await;
+                socket = new_socket;
+                keepalive = welcome.keepalive;
+            }
+            "revocation" => {
+                return Err(eventsub_error(
+                    "Twitch revoked the prediction subscriptions, the token may have been revoked",
+                ))
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::io::Write;
+
+use console::{pad_str, Alignment, Term};
+use prediction_creator::Totals;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, Time};
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::{PredictionId, PredictionStatus, Timestamp};
+
+/// How the history is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HistoryFormat {
+    /// An aligned table, for reading in the terminal
+    Table,
+    /// Comma separated values with a header row, on stdout
+    Csv,
+    /// A JSON array of rows, on stdout
+    Json,
+}
+
+/// Parses `--since`, either an RFC 3339 date and time or a date, which is taken as midnight UTC
+pub fn parse_since(value: &str) -> Result<OffsetDateTime, String> {
+    if let Ok(since) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Ok(since);
+    }
+
+    Date::parse(value, format_description!("[year]-[month]-[day]"))
+        .map(|date| date.with_time(Time::MIDNIGHT).assume_utc())
+        .map_err(|_| {
+            format!("{value:?} is neither a date like 2024-01-31 nor a date and time like 2024-01-31T20:00:00Z")
+        })
+}
+
+/// A past prediction, summarized to a single row
+#[derive(Serialize)]
+pub struct HistoryRow {
+    pub id: PredictionId,
+    pub created_at: Timestamp,
+    pub title: String,
+    pub status: PredictionStatus,
+    pub winner: Option<String>,
+    pub total_points: i64,
+    pub participants: i64,
+}
+
+impl From<&Prediction> for HistoryRow {
+    fn from(prediction: &Prediction) -> Self {
+        let winner = prediction.winning_outcome_id.as_ref().and_then(|id| {
+            prediction
+                .outcomes
+                .iter()
+                .find(|outcome| outcome.id == id.as_str())
+                .map(|outcome| outcome.title.clone())
+        });
+
+        let totals = Totals::of(prediction);
+
+        Self {
+            id: prediction.id.clone(),
+            created_at: prediction.created_at.clone(),
+            title: prediction.title.clone(),
+            status: prediction.status.clone(),
+            winner,
+            total_points: totals.channel_points,
+            participants: totals.users,
+        }
+    }
+}
+
+impl HistoryRow {
+    /// The status as Helix spells it, e.g. `RESOLVED`
+    fn status_name(&self) -> String {
+        serde_json::to_value(&self.status)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_else(|| format!("{:?}", self.status))
+    }
+}
+
+const HEADERS: [&str; 6] = [
+    "created",
+    "title",
+    "status",
+    "winner",
+    "points",
+    "participants",
+];
+
+/// Prints the rows as a table, with one column per header
+pub fn print_table(term: &mut Term, rows: &[HistoryRow]) -> anyhow::Result<()> {
+    let cells: Vec<[String; 6]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.created_at
+                    .to_utc()
+                    .format(format_description!("[year]-[month]-[day] [hour]:[minute]"))
+                    .unwrap_or_else(|_| row.created_at.to_string()),
+                row.title.clone(),
+                row.status_name(),
+                row.winner.clone().unwrap_or_default(),
+                row.total_points.to_string(),
+                row.participants.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADERS.map(console::measure_text_width);
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(console::measure_text_width(cell));
+        }
+    }
+
+    let line = |cells: [&str; 6]| -> String {
+        cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                // Numbers read best right aligned
+                let alignment = if i >= 4 {
+                    Alignment::Right
+                } else {
+                    Alignment::Left
+                };
+                pad_str(cell, width, alignment, None).into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    writeln!(term, "{}", console::style(line(HEADERS)).bold())?;
+    for row in &cells {
+        writeln!(term, "{}", line(row.each_ref().map(String::as_str)))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the rows as CSV with a header row, quoting fields as needed
+pub fn write_csv(writer: &mut impl Write, rows: &[HistoryRow]) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "id,created_at,title,status,winner,total_points,participants"
+    )?;
+    for row in rows {
+        let fields = [
+            row.id.to_string(),
+            row.created_at.to_string(),
+            row.title.clone(),
+            row.status_name(),
+            row.winner.clone().unwrap_or_default(),
+            row.total_points.to_string(),
+            row.participants.to_string(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
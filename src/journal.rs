@@ -0,0 +1,166 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::{
+    PredictionId, PredictionOutcome, PredictionOutcomeId, PredictionStatus, Timestamp, UserId,
+};
+
+use crate::{config, Error, Result};
+
+/// What was done to a prediction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalAction {
+    Created,
+    Locked,
+    Resolved,
+    Canceled,
+}
+
+impl JournalAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalAction::Created => "created",
+            JournalAction::Locked => "locked",
+            JournalAction::Resolved => "resolved",
+            JournalAction::Canceled => "canceled",
+        }
+    }
+}
+
+/// A line of the journal: an action and the prediction as Twitch returned it afterwards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: Timestamp,
+    pub action: JournalAction,
+    pub prediction_id: PredictionId,
+    pub broadcaster_id: UserId,
+    pub title: String,
+    pub status: PredictionStatus,
+    pub outcomes: Vec<PredictionOutcome>,
+    pub winning_outcome_id: Option<PredictionOutcomeId>,
+    pub prediction_window: i64,
+    pub created_at: Timestamp,
+    pub locked_at: Option<Timestamp>,
+    pub ended_at: Option<Timestamp>,
+}
+
+impl JournalEntry {
+    pub fn new(action: JournalAction, prediction: &Prediction) -> Self {
+        Self {
+            at: Timestamp::now(),
+            action,
+            prediction_id: prediction.id.clone(),
+            broadcaster_id: prediction.broadcaster_id.clone(),
+            title: prediction.title.clone(),
+            status: prediction.status.clone(),
+            outcomes: prediction.outcomes.clone(),
+            winning_outcome_id: prediction.winning_outcome_id.clone(),
+            prediction_window: prediction.prediction_window,
+            created_at: prediction.created_at.clone(),
+            locked_at: prediction.locked_at.clone(),
+            ended_at: prediction.ended_at.clone(),
+        }
+    }
+}
+
+/// Every prediction created, locked, resolved or canceled, one JSON document per line
+///
+/// Each entry is flushed to disk before the action is reported as done, so the
+/// prediction ids and outcome ids survive a crash or a closed terminal.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    /// The default location of the journal
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(config::state_dir()?.join("journal.jsonl"))
+    }
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends an entry for `action`, done to `prediction`
+    pub fn record(&self, action: JournalAction, prediction: &Prediction) -> Result<()> {
+        let entry = JournalEntry::new(action, prediction);
+
+        self.append(&entry).map_err(|e| {
+            Error::Storage(format!(
+                "Prediction {} was {} but could not be written to the journal at {}: {e}",
+                prediction.id,
+                action.as_str(),
+                self.path.display()
+            ))
+        })
+    }
+
+    fn append(&self, entry: &JournalEntry) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // A single write keeps lines whole, even with several of us appending at once
+        file.write_all(line.as_bytes())?;
+        file.sync_data()
+    }
+
+    /// Reads every entry, oldest first
+    ///
+    /// Lines that can't be read, like one cut short by a crash, are skipped.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(Error::Storage(format!(
+                    "Unable to read the journal at {}: {e}",
+                    self.path.display()
+                )))
+            }
+        };
+
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// The latest entry of the most recently created prediction of `broadcaster_id`
+    /// that wasn't resolved or canceled by us
+    pub fn last_unfinished(&self, broadcaster_id: &UserId) -> Result<Option<JournalEntry>> {
+        let entries = self.entries()?;
+        let Some(created) = entries.iter().rev().find(|entry| {
+            entry.action == JournalAction::Created && entry.broadcaster_id == *broadcaster_id
+        }) else {
+            return Ok(None);
+        };
+
+        let latest = entries
+            .iter()
+            .rev()
+            .find(|entry| entry.prediction_id == created.prediction_id)
+            .expect("the created entry to be found");
+
+        match latest.action {
+            JournalAction::Resolved | JournalAction::Canceled => Ok(None),
+            JournalAction::Created | JournalAction::Locked => Ok(Some(latest.clone())),
+        }
+    }
+}
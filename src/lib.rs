@@ -0,0 +1,19 @@
+//! Create and manage Twitch channel point predictions.
+//!
+//! [`PredictionService`] wraps the Helix predictions endpoints for a single channel, and is
+//! what the `prediction-creator` binary is built on.
+
+pub mod auth;
+pub mod config;
+pub mod dry_run;
+mod error;
+pub mod eventsub;
+pub mod journal;
+pub mod profiles;
+pub mod queue;
+mod service;
+pub mod templates;
+pub mod validation;
+
+pub use error::{Error, Result};
+pub use service::{find_outcome, PredictionService, Recorded, Totals};
@@ -1,178 +1,355 @@
+mod bot;
+mod conflict;
+mod dashboard;
+mod events;
+mod history;
+mod output;
+mod overlay;
+mod queue_runner;
+mod serve;
+mod watch;
+
 use std::env;
 use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use conflict::OnActive;
 use console::Term;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
-use twitch_api::helix::predictions::end_prediction::EndPrediction;
-use twitch_api::helix::predictions::{
-    create_prediction, end_prediction, get_predictions, Prediction,
+use history::{HistoryFormat, HistoryRow};
+use output::{
+    Output, OutputFormat, PredictionDocument, PredictionJson, PredictionsDocument,
+    ProfileStatusJson, ProfilesStatusDocument,
 };
+use prediction_creator::dry_run;
+use prediction_creator::eventsub::{self, PredictionEvents};
+use prediction_creator::journal::Journal;
+use prediction_creator::profiles::{self, Profile};
+use prediction_creator::validation::DEFAULT_PREDICTION_WINDOW;
+use prediction_creator::{auth, find_outcome, templates, validation, Error, PredictionService};
+use time::OffsetDateTime;
+use twitch_api::helix::predictions::Prediction;
 use twitch_api::helix::HelixClient;
-use twitch_api::twitch_oauth2::{TwitchToken, UserToken};
-use twitch_api::types::{PredictionIdRef, PredictionStatus, UserId};
+use twitch_api::twitch_oauth2::UserToken;
+use twitch_api::types::{PredictionId, PredictionOutcome, PredictionStatus, UserId};
 
-async fn start_prediction(
-    client: &HelixClient<'_, reqwest::Client>,
-    token: &UserToken,
-    channel_id: &UserId,
-    title: &str,
-    options: &[String],
-    prediction_window: i64,
-) -> anyhow::Result<create_prediction::CreatePredictionResponse> {
-    let request = create_prediction::CreatePredictionRequest::new();
-    let outcomes: Vec<create_prediction::NewPredictionOutcome> = options
-        .iter()
-        .map(create_prediction::NewPredictionOutcome::new)
-        .collect();
-    let body = create_prediction::CreatePredictionBody::new(
-        channel_id,
-        title,
-        &outcomes,
-        prediction_window,
-    );
+/// Create and manage Twitch channel point predictions from the command line.
+#[derive(Debug, Parser)]
+#[clap(name = "prediction-creator", after_long_help = EXIT_CODES)]
+pub struct App {
+    #[clap(subcommand)]
+    command: Command,
 
-    let response = client
-        .req_post(request, body, token)
-        .await
-        .map_err(|e| anyhow::anyhow!(e))?
-        .data;
+    /// How to write results. `json` writes one JSON document per action to stdout
+    #[clap(long, global = true, value_enum, default_value = "human")]
+    output: OutputFormat,
 
-    Ok(response)
-}
+    /// Root of the Twitch Helix API, e.g. to point at a mock server
+    #[clap(long, global = true, env = "TWITCH_HELIX_URL", default_value = DEFAULT_API_BASE_URL)]
+    api_base_url: String,
 
-async fn get_last_prediction(
-    client: &HelixClient<'_, reqwest::Client>,
-    token: &UserToken,
-    channel_id: &UserId,
-) -> anyhow::Result<Option<Prediction>> {
-    let mut request = get_predictions::GetPredictionsRequest::broadcaster_id(channel_id);
-    request.first = Some(1);
+    /// Root of the Twitch OAuth endpoints, used for logging in and validating tokens
+    #[clap(long, global = true, env = "TWITCH_OAUTH2_URL", default_value = auth::DEFAULT_AUTH_BASE_URL)]
+    auth_base_url: String,
 
-    let mut response = client.req_get(request, token).await?.data;
+    /// The EventSub WebSocket to follow the prediction on, e.g. `twitch event websocket start-server`
+    #[clap(long, global = true, env = "TWITCH_EVENTSUB_URL", default_value = eventsub::DEFAULT_EVENTSUB_URL)]
+    eventsub_url: String,
 
-    if let Some(last_prediction) = response.pop() {
-        if last_prediction.status == PredictionStatus::Active
-            || last_prediction.status == PredictionStatus::Locked
-        {
-            Ok(Some(last_prediction))
-        } else {
-            Ok(None)
-        }
-    } else {
-        Ok(None)
-    }
+    /// Manage the channel of this profile, with the token stored by `login --profile`
+    #[clap(long, global = true, env = "PREDICTION_CREATOR_PROFILE")]
+    profile: Option<String>,
+
+    /// Only act if the token is for this channel, failing with exit code 11 otherwise
+    #[clap(long, global = true, env = "TWITCH_CHANNEL")]
+    channel: Option<String>,
+
+    /// Print the Helix request `create`, `lock`, `resolve` or `cancel` would send, without sending it.
+    /// The active prediction is still read for `lock`, `resolve` and `cancel` unless `--broadcaster-id` is given
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    /// The channel to build `--dry-run` requests for, skipping the token entirely. `lock`, `resolve`
+    /// and `cancel` then need `--prediction-id`, and `resolve --winner` takes the raw outcome id
+    #[clap(long, global = true, requires = "dry_run")]
+    broadcaster_id: Option<String>,
+
+    /// The prediction to build `lock`, `resolve` or `cancel --dry-run` requests for, instead of the active one
+    #[clap(long, global = true, requires = "dry_run")]
+    prediction_id: Option<String>,
 }
 
-async fn end_prediction<'a>(
-    client: &'a HelixClient<'a, reqwest::Client>,
-    token: &'a UserToken,
-    channel_id: &'a UserId,
-    prediction_id: &'a PredictionIdRef,
-    new_status: PredictionStatus,
-    winning_outcome_id: Option<String>,
-) -> anyhow::Result<end_prediction::EndPrediction> {
-    let request = end_prediction::EndPredictionRequest::new();
-    let mut body = end_prediction::EndPredictionBody::new(channel_id, prediction_id, new_status);
-    if let Some(winning_outcome_id) = winning_outcome_id {
-        body.winning_outcome_id = Some(std::borrow::Cow::Owned(winning_outcome_id.into()));
-    }
+const EXIT_CODES: &str = "\
+Exit codes:
+  0   Success
+  1   Any other error
+  2   The prediction or the arguments are invalid
+  3   No token was found
+  4   The token is invalid or has expired
+  5   The token is missing a required scope
+  6   A prediction is already active
+  7   The channel is not a Twitch partner or affiliate
+  8   Twitch is rate limiting us
+  9   Twitch could not be reached
+  10  There is no active prediction to act on
+  11  The token can't manage the channel given with --channel";
 
-    let response = client.req_patch(request, body, token).await?.data;
+/// The default root of the Twitch Helix API, overridable with `TWITCH_HELIX_URL`
+const DEFAULT_API_BASE_URL: &str = "https://api.twitch.tv/helix/";
 
-    Ok(response)
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Log in to Twitch with a device code and store the token for later runs
+    Login {
+        /// Client ID of the Twitch application to log in with
+        #[clap(long, env = "TWITCH_CLIENT_ID")]
+        client_id: String,
+    },
+    /// Start a new prediction, see `--on-active` for what happens if one is already active
+    Create(CreateArgs),
+    /// Resolve the active prediction by picking the winning outcome
+    Resolve {
+        /// Resolve without prompting. Accepts the outcome number as shown in the menu,
+        /// the outcome title (case-insensitive) or the raw outcome id. Only the raw outcome id
+        /// with `--dry-run --broadcaster-id`
+        #[clap(long)]
+        winner: Option<String>,
+    },
+    /// Reattach to the last prediction created here that wasn't resolved or canceled, as
+    /// recorded in the journal, and resolve it like `resolve`
+    Resume {
+        /// Resolve without prompting, see `resolve --winner`
+        #[clap(long)]
+        winner: Option<String>,
+    },
+    /// Cancel the active prediction, refunding all channel points
+    Cancel,
+    /// Lock the active prediction so viewers can no longer predict
+    Lock,
+    /// Show the active prediction, if any
+    Status {
+        /// Show the active prediction of every profile instead
+        #[clap(long, conflicts_with_all = ["profile", "channel"])]
+        all_profiles: bool,
+    },
+    /// Show a live view of the active prediction, with shortcuts to lock, resolve or cancel it
+    Dashboard {
+        /// How often to refresh the prediction, in seconds
+        #[clap(long, default_value = "2")]
+        interval: u64,
+    },
+    /// List the most recent predictions
+    List {
+        /// How many predictions to list
+        #[clap(long, default_value = "5")]
+        count: usize,
+    },
+    /// Start a new prediction and resolve it from messages read from a local source
+    ///
+    /// Each message is the winning outcome, as accepted by `resolve --winner`, or "cancel".
+    Watch(WatchArgs),
+    /// Manage predictions from chat: `!predict "Title" outcome1 outcome2 [window]`, `!lock`,
+    /// `!winner <n>` and `!cancel`, accepted from the broadcaster and moderators
+    Bot {
+        /// Twitch IRC over WebSocket, e.g. to point at a local chat server
+        #[clap(long, env = "TWITCH_IRC_URL", default_value = bot::DEFAULT_IRC_URL)]
+        irc_url: String,
+    },
+    /// Run the predictions of a queue file one after the other, resuming where the last run stopped
+    Queue(queue_runner::QueueArgs),
+    /// Serve a local REST API to create, lock, resolve and cancel predictions, e.g. from a stream deck
+    Serve(serve::ServeArgs),
+    /// Serve a browser-source overlay showing the channel's predictions live, e.g. for OBS
+    Overlay(overlay::OverlayArgs),
+    /// Show all past predictions with their winner, points and participants
+    History {
+        /// Only include predictions created on or after this date (2024-01-31) or time (2024-01-31T20:00:00Z)
+        #[clap(long, value_parser = history::parse_since)]
+        since: Option<OffsetDateTime>,
+        /// Include at most this many predictions, newest first
+        #[clap(long)]
+        limit: Option<usize>,
+        /// How to write the history. `csv` and `json` write to stdout, for exporting
+        #[clap(long, value_enum, default_value = "table")]
+        format: HistoryFormat,
+    },
 }
 
-/// A very simple utility to search for a string across multiple files.
-#[derive(Debug, Parser)]
-#[clap(name = "prediction-creator")]
-pub struct App {
-    /// The title of the prediction
-    #[clap(long)]
-    title: String,
+#[derive(Debug, Args)]
+struct CreateArgs {
+    /// The title of the prediction, at most 45 characters
+    #[clap(long, required_unless_present = "template")]
+    title: Option<String>,
 
-    /// Outcomes. At least 2 must be provided, at most 5 must be provided
+    /// Outcomes, at most 25 characters each. At least 2 must be provided, at most 5 must be provided
     #[clap(long)]
     outcome: Vec<String>,
 
-    /// Duration of the outcome in seconds
-    #[clap(long, default_value = "30")]
-    prediction_window: i64,
-}
+    /// Duration of the outcome in seconds, between 30 and 1800 [default: 30]
+    #[clap(long)]
+    prediction_window: Option<i64>,
+
+    /// Name of a template to fill in the prediction from. Other flags override the template
+    #[clap(long)]
+    template: Option<String>,
 
-fn parse_args() -> anyhow::Result<App> {
-    let app = App::try_parse()?;
+    /// Path to the templates file [default: ~/.config/prediction-creator/templates.toml]
+    #[clap(long, requires = "template")]
+    templates_file: Option<PathBuf>,
 
-    let num_outcomes = app.outcome.len();
+    /// What to do when another prediction is already active. Asks when not given,
+    /// or fails if there is nobody to ask
+    #[clap(long, value_enum)]
+    on_active: Option<OnActive>,
 
-    if num_outcomes < 2 {
-        anyhow::bail!(
-            "You must provide at least 2 outcomes with --outcome, you provided {num_outcomes}"
-        )
-    }
+    /// The winning outcome of the active prediction for `--on-active resolve-then-replace`,
+    /// as accepted by `resolve --winner`. Asked for when not given
+    #[clap(long)]
+    active_winner: Option<String>,
+}
 
-    if num_outcomes > 5 {
-        anyhow::bail!(
-            "You may provide at most 5 outcomes with --outcome, you provided {num_outcomes}"
-        )
-    }
+#[derive(Debug, Args)]
+struct WatchArgs {
+    #[clap(flatten)]
+    create: CreateArgs,
 
-    Ok(app)
+    #[clap(flatten)]
+    source: watch::SourceArgs,
+
+    /// Cancel the prediction if no winner was received after this many seconds
+    #[clap(long, default_value = "3600")]
+    timeout: u64,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let app = parse_args()?;
+impl CreateArgs {
+    /// Fills in everything not given on the command line from the selected template
+    fn apply_template(&mut self) -> anyhow::Result<()> {
+        let template = match self.template {
+            Some(ref name) => {
+                let path = match self.templates_file {
+                    Some(ref path) => path.clone(),
+                    None => templates::default_path()?,
+                };
+                // A templates file that can't be read is as much a bad --template as an unknown name
+                templates::find(&path, name).map_err(|e| match e {
+                    Error::Storage(message) => Error::InvalidArguments(message),
+                    e => e,
+                })?
+            }
+            None => templates::Template::default(),
+        };
 
-    let mut term = Term::stdout();
+        if self.title.is_none() {
+            self.title = template.title;
+        }
+        if self.outcome.is_empty() {
+            self.outcome = template.outcomes;
+        }
+        self.prediction_window = self
+            .prediction_window
+            .or(template.prediction_window)
+            .or(Some(DEFAULT_PREDICTION_WINDOW));
 
-    // Create the HelixClient, which is used to make requests to the Twitch API
-    let client: HelixClient<reqwest::Client> = HelixClient::default();
-    let access_token = env::var("TWITCH_ACCESS_TOKEN")?;
-    // Create a UserToken, which is used to authenticate requests
-    let token = UserToken::from_token(&client, access_token.into()).await?;
+        if self.title.is_none() {
+            return Err(invalid_arguments(
+                "You must provide a title with --title or in the template",
+            ));
+        }
 
-    let broadcaster = token.validate_token(&client).await?;
-    let broadcaster_login = broadcaster.login.expect("token to contain a login");
-    let broadcaster_user_id = broadcaster.user_id.expect("token to contain a user id");
+        Ok(())
+    }
+}
 
-    let prediction = if let Some(current_prediction) =
-        get_last_prediction(&client, &token, &broadcaster_user_id).await?
+/// Fills in templates and validates the arguments, before anything is sent to Twitch
+fn validate_args(app: &mut App) -> anyhow::Result<()> {
+    if let Command::Create(ref mut args)
+    | Command::Watch(WatchArgs {
+        create: ref mut args,
+        ..
+    }) = app.command
     {
-        writeln!(
-            term,
-            "Found already active prediction: {}",
-            current_prediction.title
-        )?;
-        current_prediction
-    } else {
-        writeln!(
-            term,
-            "Starting prediction for {} ({}): {}",
-            console::style(broadcaster_login).bold(),
-            broadcaster_user_id,
-            app.title
+        args.apply_template()?;
+
+        if args.active_winner.is_some() && args.on_active != Some(OnActive::ResolveThenReplace) {
+            return Err(invalid_arguments(
+                "--active-winner only applies to --on-active resolve-then-replace",
+            ));
+        }
+
+        validation::validate_prediction(
+            args.title.as_deref().unwrap_or_default(),
+            &args.outcome,
+            args.prediction_window.unwrap_or(DEFAULT_PREDICTION_WINDOW),
         )?;
+    }
 
-        start_prediction(
-            &client,
-            &token,
-            &broadcaster_user_id,
-            &app.title,
-            &app.outcome,
-            app.prediction_window,
-        )
-        .await?
-    };
-    let options = prediction.outcomes;
+    if app.dry_run {
+        match app.command {
+            Command::Create(_) | Command::Lock | Command::Cancel => {}
+            Command::Resolve { winner: Some(_) } => {}
+            Command::Resolve { winner: None } => {
+                return Err(invalid_arguments(
+                    "resolve --dry-run needs the winning outcome with --winner",
+                ));
+            }
+            _ => {
+                return Err(invalid_arguments(
+                    "--dry-run only applies to create, lock, resolve and cancel",
+                ))
+            }
+        }
+        let create = matches!(app.command, Command::Create(_));
+        if app.prediction_id.is_some() && create {
+            return Err(invalid_arguments(
+                "--prediction-id only applies to lock, resolve and cancel",
+            ));
+        }
+        if app.broadcaster_id.is_some() && app.prediction_id.is_none() && !create {
+            return Err(invalid_arguments(
+                "--broadcaster-id needs --prediction-id for lock, resolve and cancel, as the active prediction can't be read without the token",
+            ));
+        }
+    }
 
-    let mut items: Vec<String> = options
+    if let Command::List { count }
+    | Command::History {
+        limit: Some(count), ..
+    } = app.command
+    {
+        if count == 0 {
+            return Err(invalid_arguments("At least one prediction must be listed"));
+        }
+    }
+
+    Ok(())
+}
+
+fn invalid_arguments(message: &str) -> anyhow::Error {
+    Error::InvalidArguments(message.to_string()).into()
+}
+
+/// An entry picked from the interactive resolve menu
+enum MenuAction {
+    Resolve(PredictionOutcome),
+    Lock,
+    Cancel,
+}
+
+/// Prompts for the winning outcome, offering to lock the prediction first if it is still active
+fn select_action(outcomes: &[PredictionOutcome], locked: bool) -> anyhow::Result<MenuAction> {
+    let mut items: Vec<String> = outcomes
         .iter()
         .enumerate()
         .map(|(i, outcome)| format!("[{}] {}", i + 1, outcome.title.clone()))
         .collect();
 
+    if !locked {
+        items.push("LOCK".to_string());
+    }
     items.push("CANCEL".to_string());
 
     let selection = Select::with_theme(&ColorfulTheme::default())
@@ -181,42 +358,700 @@ async fn main() -> anyhow::Result<()> {
         .items(&items)
         .interact()?;
 
-    let response = if let Some(selected_outcome) = options.get(selection) {
-        writeln!(term, "Resolving with this outcome {selected_outcome:?}")?;
-        end_prediction(
-            &client,
-            &token,
-            &broadcaster_user_id,
-            &prediction.id,
-            PredictionStatus::Resolved,
-            Some(selected_outcome.id.clone()),
-        )
-        .await?
+    if let Some(outcome) = outcomes.get(selection) {
+        Ok(MenuAction::Resolve(outcome.clone()))
+    } else if !locked && selection == outcomes.len() {
+        Ok(MenuAction::Lock)
+    } else {
+        Ok(MenuAction::Cancel)
+    }
+}
+
+fn print_prediction(term: &mut Term, prediction: &Prediction) -> anyhow::Result<()> {
+    writeln!(
+        term,
+        "{} ({:?}, {})",
+        console::style(&prediction.title).bold(),
+        prediction.status,
+        prediction.id
+    )?;
+    for (i, outcome) in prediction.outcomes.iter().enumerate() {
+        writeln!(term, "  [{}] {}", i + 1, outcome.title)?;
+    }
+
+    Ok(())
+}
+
+/// How many of the top predictors to show for each outcome
+const TOP_PREDICTORS_SHOWN: usize = 3;
+
+/// Prints a summary of a prediction that was just locked, resolved or canceled
+fn print_end_prediction(
+    term: &mut Term,
+    prediction: &Prediction,
+    verb: &str,
+) -> anyhow::Result<()> {
+    writeln!(
+        term,
+        "Successfully {verb} prediction {}",
+        console::style(&prediction.title).bold()
+    )?;
+
+    let winner = prediction.winning_outcome_id.as_ref().and_then(|id| {
+        prediction
+            .outcomes
+            .iter()
+            .find(|outcome| outcome.id == id.as_str())
+    });
+    if let Some(winner) = winner {
+        writeln!(
+            term,
+            "Winner: {}",
+            console::style(&winner.title).green().bold()
+        )?;
+    }
+
+    for (i, outcome) in prediction.outcomes.iter().enumerate() {
+        let is_winner = winner.is_some_and(|winner| winner.id == outcome.id);
+        writeln!(
+            term,
+            "  [{}] {}: {} users, {} channel points{}",
+            i + 1,
+            outcome.title,
+            outcome.users.unwrap_or_default(),
+            outcome.channel_points.unwrap_or_default(),
+            if is_winner { " (winner)" } else { "" }
+        )?;
+
+        for predictor in outcome
+            .top_predictors
+            .iter()
+            .flatten()
+            .take(TOP_PREDICTORS_SHOWN)
+        {
+            match predictor.channel_points_won {
+                Some(won) if won > 0 => writeln!(
+                    term,
+                    "        {} bet {} and won {}",
+                    predictor.name, predictor.channel_points_used, won
+                )?,
+                _ => writeln!(
+                    term,
+                    "        {} bet {}",
+                    predictor.name, predictor.channel_points_used
+                )?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts the prediction described by `args`, first dealing with any prediction that is already active
+///
+/// Returns the prediction that was started, or the active one if it was adopted.
+async fn create(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    args: CreateArgs,
+) -> anyhow::Result<Prediction> {
+    let title = args.title.expect("validate_args to fill in the title");
+    let prediction_window = args
+        .prediction_window
+        .expect("validate_args to fill in the prediction window");
+
+    if let Some(current_prediction) = service.current().await? {
+        let on_active = match args.on_active {
+            Some(on_active) => on_active,
+            None if console::user_attended_stderr() => conflict::ask(
+                term,
+                &current_prediction,
+                &title,
+                &args.outcome,
+                prediction_window,
+            )?,
+            None => OnActive::Fail,
+        };
+
+        match on_active {
+            OnActive::Adopt => {
+                writeln!(
+                    term,
+                    "Found already active prediction: {}",
+                    current_prediction.title
+                )?;
+                print_prediction(term, &current_prediction)?;
+                output.emit(&PredictionDocument::new(
+                    "found_active",
+                    Some(&current_prediction),
+                ));
+                return Ok(current_prediction);
+            }
+            OnActive::Fail => {
+                return Err(Error::PredictionAlreadyActive(current_prediction.title).into())
+            }
+            OnActive::CancelAndReplace => {
+                writeln!(
+                    term,
+                    "{} {}",
+                    console::style("Cancelling").bold(),
+                    current_prediction.title
+                )?;
+                let ended = output.journaled(service.cancel(&current_prediction.id).await?);
+                print_end_prediction(term, &ended, "canceled")?;
+                output.emit(&PredictionDocument::new("canceled", Some(&ended)));
+            }
+            OnActive::ResolveThenReplace => {
+                let winner = match args.active_winner {
+                    Some(ref winner) => find_outcome(&current_prediction.outcomes, winner)?,
+                    None if console::user_attended_stderr() => {
+                        conflict::ask_winner(&current_prediction.outcomes)?
+                    }
+                    None => {
+                        return Err(invalid_arguments(
+                            "--on-active resolve-then-replace needs --active-winner when not run interactively",
+                        ))
+                    }
+                };
+                writeln!(
+                    term,
+                    "Resolving {} with this outcome {}",
+                    current_prediction.title,
+                    console::style(&winner.title).bold()
+                )?;
+                let ended =
+                    output.journaled(service.resolve(&current_prediction.id, &winner.id).await?);
+                print_end_prediction(term, &ended, "resolved")?;
+                output.emit(&PredictionDocument::new("resolved", Some(&ended)));
+            }
+        }
+    }
+
+    writeln!(
+        term,
+        "Starting prediction for {} ({}): {}",
+        console::style(&service.token().login).bold(),
+        service.broadcaster_id(),
+        title
+    )?;
+
+    let prediction = output.journaled(
+        service
+            .create(&title, &args.outcome, prediction_window)
+            .await?,
+    );
+    print_prediction(term, &prediction)?;
+    output.emit(&PredictionDocument::new("created", Some(&prediction)));
+
+    Ok(prediction)
+}
+
+/// Resolves, locks or cancels `prediction`, from `winner` or from the menu
+async fn resolve(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    mut prediction: Prediction,
+    winner: Option<String>,
+    mut events: Option<PredictionEvents>,
+) -> anyhow::Result<()> {
+    let action = loop {
+        let action = if let Some(ref winner) = winner {
+            MenuAction::Resolve(find_outcome(&prediction.outcomes, winner)?.clone())
+        } else {
+            let outcomes = prediction.outcomes.clone();
+            let locked = prediction.status == PredictionStatus::Locked;
+            let selected = events::prompt(term, &mut prediction, &mut events, move || {
+                select_action(&outcomes, locked)
+            });
+            match selected.await? {
+                Some(action) => action,
+                None => {
+                    print_end_prediction(term, &prediction, "ended")?;
+                    output.emit(&PredictionDocument::new(
+                        "ended_elsewhere",
+                        Some(&prediction),
+                    ));
+                    return Ok(());
+                }
+            }
+        };
+
+        if let MenuAction::Lock = action {
+            if prediction.status == PredictionStatus::Locked {
+                writeln!(term, "The prediction was locked in the meantime")?;
+                continue;
+            }
+            writeln!(term, "{}", console::style("Locking").bold())?;
+            prediction = output.journaled(service.lock(&prediction.id).await?);
+            print_end_prediction(term, &prediction, "locked")?;
+            output.emit(&PredictionDocument::new("locked", Some(&prediction)));
+            continue;
+        }
+
+        break action;
+    };
+
+    if let MenuAction::Resolve(selected_outcome) = action {
+        writeln!(
+            term,
+            "Resolving with this outcome {}",
+            console::style(&selected_outcome.title).bold()
+        )?;
+        let ended = output.journaled(
+            service
+                .resolve(&prediction.id, &selected_outcome.id)
+                .await?,
+        );
+        print_end_prediction(term, &ended, "resolved")?;
+        output.emit(&PredictionDocument::new("resolved", Some(&ended)));
     } else {
         writeln!(term, "{}", console::style("Cancelling").bold())?;
-        end_prediction(
-            &client,
-            &token,
-            &broadcaster_user_id,
-            &prediction.id,
-            PredictionStatus::Canceled,
-            None,
-        )
-        .await?
+        let ended = output.journaled(service.cancel(&prediction.id).await?);
+        print_end_prediction(term, &ended, "canceled")?;
+        output.emit(&PredictionDocument::new("canceled", Some(&ended)));
+    }
+
+    Ok(())
+}
+
+/// Loads the token from `TWITCH_ACCESS_TOKEN`, falling back to the one stored by `login`
+async fn load_token(
+    client: &HelixClient<'_, reqwest::Client>,
+) -> prediction_creator::Result<UserToken> {
+    let token = if let Ok(access_token) = env::var("TWITCH_ACCESS_TOKEN") {
+        UserToken::from_token(client, access_token.into()).await?
+    } else {
+        match auth::StoredToken::load()? {
+            Some(stored) => {
+                stored
+                    .to_user_token(client, &auth::StoredToken::path()?)
+                    .await?
+            }
+            None => return Err(Error::NoToken),
+        }
+    };
+
+    auth::check_scopes(&token)?;
+
+    Ok(token)
+}
+
+/// Loads the token stored for the profile called `name`, making sure it is still for the profile's channel
+async fn load_profile_token(
+    client: &HelixClient<'_, reqwest::Client>,
+    name: &str,
+) -> anyhow::Result<UserToken> {
+    let profile = profiles::find(&profiles::default_path()?, name)?;
+    let path = profiles::token_path(name)?;
+    let Some(stored) = auth::StoredToken::load_from(&path)? else {
+        return Err(Error::NoProfileToken(name.to_string()).into());
     };
+    if stored.user_id != profile.broadcaster_id {
+        return Err(Error::InvalidToken(format!(
+            "The token of profile {name:?} is for {}, but the profile manages {}. Run `prediction-creator login --profile {name}` as {}",
+            stored.login, profile.login, profile.login
+        ))
+        .into());
+    }
+
+    let token = stored.to_user_token(client, &path).await?;
+    auth::check_scopes(&token)?;
+
+    Ok(token)
+}
+
+/// Creates the service for the channel of `profile`, or of the token from [`load_token`] without one
+async fn connect(profile: Option<&str>) -> anyhow::Result<PredictionService> {
+    // Create the HelixClient, which is used to make requests to the Twitch API
+    let client: HelixClient<reqwest::Client> = HelixClient::default();
+
+    // Create a UserToken, which is used to authenticate requests
+    let service = match profile {
+        Some(name) => {
+            let token = load_profile_token(&client, name).await?;
+            PredictionService::new(client, token).with_token_path(profiles::token_path(name)?)
+        }
+        None => PredictionService::new(client.clone(), load_token(&client).await?),
+    };
+
+    Ok(service)
+}
+
+/// Prints the Helix request the command would send instead of sending it
+async fn dry_run(term: &mut Term, output: &Output, app: &App) -> anyhow::Result<()> {
+    let request = if let Command::Create(ref args) = app.command {
+        let broadcaster_id = match app.broadcaster_id {
+            Some(ref id) => UserId::from(id.clone()),
+            None => connect_to_channel(app).await?.broadcaster_id().clone(),
+        };
+        dry_run::create(
+            &broadcaster_id,
+            args.title.as_deref().unwrap_or_default(),
+            &args.outcome,
+            args.prediction_window.unwrap_or(DEFAULT_PREDICTION_WINDOW),
+        )?
+    } else {
+        let (broadcaster_id, prediction_id, prediction) = match app.broadcaster_id {
+            // Without the token there is no prediction to check the request against
+            Some(ref id) => {
+                let prediction_id = app
+                    .prediction_id
+                    .clone()
+                    .expect("validate_args to require --prediction-id");
+                (
+                    UserId::from(id.clone()),
+                    PredictionId::from(prediction_id),
+                    None,
+                )
+            }
+            None => {
+                let mut service = connect_to_channel(app).await?;
+                let prediction = match app.prediction_id {
+                    Some(ref id) => service.get(&PredictionId::from(id.clone())).await?,
+                    None => service.require_current().await?,
+                };
+                (
+                    service.broadcaster_id().clone(),
+                    prediction.id.clone(),
+                    Some(prediction),
+                )
+            }
+        };
+        let (status, winning_outcome_id) = match (&app.command, &prediction) {
+            (Command::Lock, Some(prediction)) if prediction.status == PredictionStatus::Locked => {
+                return Err(Error::AlreadyLocked(prediction.title.clone()).into());
+            }
+            (Command::Lock, _) => (PredictionStatus::Locked, None),
+            (
+                Command::Resolve {
+                    winner: Some(winner),
+                },
+                Some(prediction),
+            ) => {
+                let outcome = find_outcome(&prediction.outcomes, winner)?;
+                (PredictionStatus::Resolved, Some(outcome.id.clone()))
+            }
+            // Outcome numbers and titles can't be looked up offline, so this is the outcome id
+            (
+                Command::Resolve {
+                    winner: Some(winner),
+                },
+                None,
+            ) => (PredictionStatus::Resolved, Some(winner.trim().to_string())),
+            (Command::Cancel, _) => (PredictionStatus::Canceled, None),
+            _ => unreachable!("--dry-run is checked in validate_args"),
+        };
+        dry_run::end(&broadcaster_id, &prediction_id, status, winning_outcome_id)
+    };
+
+    writeln!(
+        term,
+        "{} {} {}",
+        console::style("Would send").bold(),
+        request.method,
+        request.endpoint
+    )?;
+    writeln!(term, "{}", serde_json::to_string_pretty(&request.body)?)?;
+    output.emit(&serde_json::json!({
+        "action": "dry_run",
+        "request": request,
+    }));
+
+    Ok(())
+}
+
+/// Like [`connect`], for the profile of `app`, checking the token is for `--channel` if given
+async fn connect_to_channel(app: &App) -> anyhow::Result<PredictionService> {
+    let mut service = connect(app.profile.as_deref()).await?;
+    if let Some(ref channel) = app.channel {
+        service.check_channel(channel).await?;
+    }
+
+    Ok(service)
+}
+
+/// Shows the active prediction of every profile, carrying on past profiles that fail
+async fn status_all_profiles(term: &mut Term, output: &Output) -> anyhow::Result<()> {
+    let path = profiles::default_path()?;
+    let all = profiles::load(&path)?;
+    if all.is_empty() {
+        anyhow::bail!(
+            "No profiles in {}, add one with `prediction-creator login --profile <name>`",
+            path.display()
+        );
+    }
+
+    let mut statuses = Vec::new();
+    for (name, profile) in all {
+        let prediction = match connect(Some(&name)).await {
+            Ok(mut service) => service.current().await.map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+
+        writeln!(
+            term,
+            "{} ({})",
+            console::style(&name).bold().underlined(),
+            profile.login
+        )?;
+        match prediction {
+            Ok(Some(ref prediction)) => print_prediction(term, prediction)?,
+            Ok(None) => writeln!(term, "No active prediction")?,
+            Err(ref e) => writeln!(term, "{} {e:#}", console::style("Error:").red().bold())?,
+        }
+        writeln!(term)?;
+
+        statuses.push(ProfileStatusJson::new(name, profile, prediction));
+    }
+
+    output.emit(&ProfilesStatusDocument {
+        action: "status",
+        profiles: statuses,
+    });
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let app = App::parse();
+    let output = Output::new(app.output);
+
+    match run(app, &output).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            output.error(&e);
+            ExitCode::from(output::exit_code(&e))
+        }
+    }
+}
+
+async fn run(mut app: App, output: &Output) -> anyhow::Result<()> {
+    validate_args(&mut app)?;
 
-    match response {
-        EndPrediction::Success(ref _success) => {
-            // TODO: Print successful outcome
-            writeln!(term, "Successfully ended prediction")?;
+    let mut term = output.term();
+
+    // twitch_api and twitch_oauth2 read their endpoints from the environment, and expect a trailing slash
+    let api_base_url = format!("{}/", app.api_base_url.trim_end_matches('/'));
+    env::set_var("TWITCH_HELIX_URL", &api_base_url);
+    let auth_base_url = format!("{}/", app.auth_base_url.trim_end_matches('/'));
+    env::set_var("TWITCH_OAUTH2_URL", &auth_base_url);
+
+    if let Command::Login { ref client_id } = app.command {
+        // Check the profile name before the user authorizes, or the new token would be lost
+        let profile_token = match app.profile {
+            Some(ref name) => Some((name, profiles::token_path(name)?)),
+            None => None,
+        };
+        let stored = auth::login(&mut term, &auth_base_url, client_id).await?;
+        let path = match profile_token {
+            Some((name, path)) => {
+                stored.save_to(&path)?;
+                let profile = Profile {
+                    login: stored.login.clone(),
+                    broadcaster_id: stored.user_id.clone(),
+                };
+                profiles::save(&profiles::default_path()?, name, profile)?;
+                path
+            }
+            None => stored.save()?,
+        };
+        writeln!(
+            term,
+            "Logged in as {}, token stored in {}",
+            console::style(&stored.login).bold(),
+            path.display()
+        )?;
+        output.emit(&serde_json::json!({
+            "action": "logged_in",
+            "login": stored.login,
+            "user_id": stored.user_id,
+            "profile": app.profile,
+            "token_path": path,
+        }));
+        return Ok(());
+    }
+
+    if let Command::Status { all_profiles: true } = app.command {
+        return status_all_profiles(&mut term, output).await;
+    }
+
+    if app.dry_run {
+        return dry_run(&mut term, output, &app).await;
+    }
+
+    let mut service = connect_to_channel(&app).await?;
+    // Like failing to write it, not knowing where to keep the journal doesn't stop anything
+    match Journal::default_path() {
+        Ok(path) => service = service.with_journal(Journal::new(path)),
+        Err(e) => output.warn("journal", format!("Not journaling predictions: {e}")),
+    }
+
+    match app.command {
+        Command::Login { .. } => unreachable!("login is handled before loading the token"),
+        Command::Create(args) => {
+            create(&mut term, output, &mut service, args).await?;
+        }
+        Command::Watch(args) => {
+            let messages = watch::listen(args.source).await?;
+            let events = events::subscribe(&mut term, &mut service, &app.eventsub_url).await?;
+            let prediction = create(&mut term, output, &mut service, args.create).await?;
+            watch::run(
+                &mut term,
+                output,
+                &mut service,
+                prediction,
+                messages,
+                events,
+                Duration::from_secs(args.timeout),
+            )
+            .await?;
+        }
+        Command::Resolve { winner } => {
+            // Only the menu waits long enough for the prediction to change elsewhere
+            let events = match winner {
+                Some(_) => None,
+                None => events::subscribe(&mut term, &mut service, &app.eventsub_url).await?,
+            };
+            let prediction = service.require_current().await?;
+            resolve(&mut term, output, &mut service, prediction, winner, events).await?;
+        }
+        Command::Resume { winner } => {
+            // Without a journal there is nothing to resume, so report why there is none
+            let journal = match service.journal() {
+                Some(journal) => journal.clone(),
+                None => Journal::new(Journal::default_path()?),
+            };
+            let entry = journal
+                .last_unfinished(service.broadcaster_id())?
+                .ok_or(Error::NoActivePrediction)?;
+            writeln!(
+                term,
+                "Reattaching to {}, {} at {}",
+                console::style(&entry.title).bold(),
+                entry.action.as_str(),
+                entry.at.as_str()
+            )?;
+
+            let events = match winner {
+                Some(_) => None,
+                None => events::subscribe(&mut term, &mut service, &app.eventsub_url).await?,
+            };
+            let prediction = service.get(&entry.prediction_id).await?;
+            if matches!(
+                prediction.status,
+                PredictionStatus::Resolved | PredictionStatus::Canceled
+            ) {
+                print_end_prediction(&mut term, &prediction, "ended")?;
+                output.emit(&PredictionDocument::new(
+                    "ended_elsewhere",
+                    Some(&prediction),
+                ));
+                return Ok(());
+            }
+            resolve(&mut term, output, &mut service, prediction, winner, events).await?;
+        }
+        Command::Cancel => {
+            let prediction = service.require_current().await?;
+            writeln!(
+                term,
+                "{} {}",
+                console::style("Cancelling").bold(),
+                prediction.title
+            )?;
+            let ended = output.journaled(service.cancel(&prediction.id).await?);
+            print_end_prediction(&mut term, &ended, "canceled")?;
+            output.emit(&PredictionDocument::new("canceled", Some(&ended)));
+        }
+        Command::Lock => {
+            let prediction = service.require_current().await?;
+            if prediction.status == PredictionStatus::Locked {
+                return Err(Error::AlreadyLocked(prediction.title).into());
+            }
+            writeln!(
+                term,
+                "{} {}",
+                console::style("Locking").bold(),
+                prediction.title
+            )?;
+            let ended = output.journaled(service.lock(&prediction.id).await?);
+            print_end_prediction(&mut term, &ended, "locked")?;
+            output.emit(&PredictionDocument::new("locked", Some(&ended)));
+        }
+        Command::Status { .. } => {
+            let prediction = service.current().await?;
+            match prediction {
+                Some(ref prediction) => print_prediction(&mut term, prediction)?,
+                None => writeln!(term, "No active prediction")?,
+            }
+            output.emit(&PredictionDocument::new("status", prediction.as_ref()));
+        }
+        Command::Dashboard { interval } => {
+            let events = events::subscribe(&mut term, &mut service, &app.eventsub_url).await?;
+            let prediction = service.require_current().await?;
+            let prediction = dashboard::run(
+                &mut term,
+                &mut service,
+                prediction,
+                events,
+                Duration::from_secs(interval.max(1)),
+            )
+            .await?;
+            output.emit(&PredictionDocument::new(
+                "dashboard_closed",
+                Some(&prediction),
+            ));
+        }
+        Command::List { count } => {
+            let predictions = service.history(Some(count), None).await?;
+            if predictions.is_empty() {
+                writeln!(term, "No predictions found")?;
+            }
+            for prediction in &predictions {
+                print_prediction(&mut term, prediction)?;
+            }
+            output.emit(&PredictionsDocument {
+                action: "list",
+                predictions: predictions.iter().map(PredictionJson::from).collect(),
+            });
+        }
+        Command::Queue(args) => {
+            let events = events::subscribe(&mut term, &mut service, &app.eventsub_url).await?;
+            queue_runner::run(&mut term, output, &mut service, args, events).await?;
+        }
+        Command::Serve(args) => {
+            serve::run(&mut term, *output, service, args).await?;
+        }
+        Command::Overlay(args) => {
+            let events = events::subscribe(&mut term, &mut service, &app.eventsub_url).await?;
+            overlay::run(&mut term, output, &mut service, args, events).await?;
         }
-        EndPrediction::MissingQuery => {
-            writeln!(term, "ERROR: Bad prediction body: {:?}", response)?;
+        Command::Bot { ref irc_url } => {
+            auth::check_chat_scopes(service.token())?;
+            bot::run(&mut term, output, &mut service, irc_url).await?;
         }
-        EndPrediction::AuthFailed => {
-            writeln!(term, "ERROR: Auth failed: {:?}", response)?;
+        Command::History {
+            since,
+            limit,
+            format,
+        } => {
+            let predictions = service.history(limit, since).await?;
+            let rows: Vec<HistoryRow> = predictions.iter().map(HistoryRow::from).collect();
+            match format {
+                HistoryFormat::Table if rows.is_empty() => writeln!(term, "No predictions found")?,
+                HistoryFormat::Table => history::print_table(&mut term, &rows)?,
+                HistoryFormat::Csv => history::write_csv(&mut std::io::stdout().lock(), &rows)?,
+                HistoryFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            }
+            if format == HistoryFormat::Table {
+                output.emit(&PredictionsDocument {
+                    action: "history",
+                    predictions: predictions.iter().map(PredictionJson::from).collect(),
+                });
+            }
         }
-        unknown => unimplemented!("Unimplemented end_prediction response {unknown:?}"),
     }
 
     Ok(())
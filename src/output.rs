@@ -0,0 +1,295 @@
+use std::io::Write;
+
+use console::Term;
+use serde::Serialize;
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::{
+    PredictionId, PredictionOutcome, PredictionOutcomeId, PredictionStatus, Timestamp, UserId,
+    UserName,
+};
+
+use prediction_creator::profiles::Profile;
+use prediction_creator::validation::{ValidationErrors, Violation};
+use prediction_creator::{Error, Recorded};
+
+/// How results are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text on stdout
+    Human,
+    /// One JSON document per action on stdout, with human readable text and prompts on stderr
+    Json,
+}
+
+/// Where human readable text and machine readable documents are written
+#[derive(Clone, Copy)]
+pub struct Output {
+    format: OutputFormat,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Self { format }
+    }
+
+    /// The terminal human readable text is written to
+    ///
+    /// This is stderr in JSON mode, so stdout only ever contains JSON documents.
+    pub fn term(&self) -> Term {
+        match self.format {
+            OutputFormat::Human => Term::stdout(),
+            OutputFormat::Json => Term::stderr(),
+        }
+    }
+
+    /// Writes `document` as a single line of JSON to stdout in JSON mode
+    pub fn emit(&self, document: &impl Serialize) {
+        if self.format == OutputFormat::Json {
+            match serde_json::to_string(document) {
+                Ok(json) => println!("{json}"),
+                Err(e) => eprintln!("Unable to serialize output: {e}"),
+            }
+        }
+    }
+
+    /// Reports an error that made the current action fail
+    pub fn error(&self, error: &anyhow::Error) {
+        match self.format {
+            OutputFormat::Human => {
+                eprintln!("{} {error:#}", console::style("Error:").red().bold());
+            }
+            OutputFormat::Json => self.emit(&ErrorDocument {
+                action: "error",
+                error: ErrorJson::new(error),
+            }),
+        }
+    }
+
+    /// Reports a problem that doesn't make the current action fail
+    pub fn warn(&self, code: &'static str, message: impl ToString) {
+        let message = message.to_string();
+        let _ = writeln!(
+            self.term(),
+            "{} {message}",
+            console::style("Warning:").yellow().bold()
+        );
+        self.emit(&WarningDocument {
+            action: "warning",
+            warning: ErrorJson::other(code, message),
+        });
+    }
+
+    /// Returns the prediction of an action Twitch accepted, warning if it wasn't journaled
+    pub fn journaled(&self, recorded: Recorded) -> Prediction {
+        if let Some(ref e) = recorded.journal_error {
+            self.warn("journal", e);
+        }
+
+        recorded.prediction
+    }
+}
+
+/// A problem that didn't make the current action fail
+#[derive(Serialize)]
+struct WarningDocument {
+    action: &'static str,
+    warning: ErrorJson,
+}
+
+/// A stable, machine readable name for what went wrong
+fn error_code(error: &anyhow::Error) -> &'static str {
+    if validation_errors(error).is_some() {
+        return "invalid_prediction";
+    }
+
+    match error.downcast_ref::<Error>() {
+        Some(Error::InvalidArguments(_)) => "invalid_arguments",
+        Some(Error::NoActivePrediction) => "no_active_prediction",
+        Some(Error::PredictionNotFound(_)) => "prediction_not_found",
+        Some(Error::AlreadyLocked(_)) => "already_locked",
+        Some(Error::UnknownOutcome { .. }) => "unknown_outcome",
+        Some(Error::AmbiguousOutcome { .. }) => "ambiguous_outcome",
+        Some(Error::NoToken | Error::NoProfileToken(_)) => "no_token",
+        Some(Error::UnknownProfile { .. }) => "unknown_profile",
+        Some(Error::InvalidToken(_)) => "invalid_token",
+        Some(Error::MissingScope { .. }) => "missing_scope",
+        Some(Error::PredictionAlreadyActive(_)) => "prediction_already_active",
+        Some(Error::NotAffiliate(_)) => "not_affiliate",
+        Some(Error::RateLimited) => "rate_limited",
+        Some(Error::Network(_)) => "network",
+        Some(Error::BadRequest) => "bad_request",
+        Some(Error::UnexpectedResponse(_)) => "unexpected_response",
+        Some(Error::EventSub(_)) => "eventsub",
+        Some(Error::UnknownChannel(_)) => "unknown_channel",
+        Some(Error::WrongChannel { .. }) => "wrong_channel",
+        _ => "error",
+    }
+}
+
+/// The process exit code for `error`, as documented on [`Error`]
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    match error.downcast_ref::<Error>() {
+        Some(error) => error.exit_code(),
+        None if validation_errors(error).is_some() => 2,
+        None => 1,
+    }
+}
+
+/// The validation errors behind `error`, whether they were raised by the CLI or the library
+fn validation_errors(error: &anyhow::Error) -> Option<&ValidationErrors> {
+    match error.downcast_ref::<Error>() {
+        Some(Error::Invalid(errors) | Error::InvalidQueued { errors, .. }) => Some(errors),
+        _ => error.downcast_ref::<ValidationErrors>(),
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorDocument {
+    action: &'static str,
+    error: ErrorJson,
+}
+
+/// An error as written in JSON documents
+#[derive(Serialize)]
+pub struct ErrorJson {
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    violations: Option<Vec<ViolationJson>>,
+}
+
+impl ErrorJson {
+    pub fn new(error: &anyhow::Error) -> Self {
+        Self {
+            code: error_code(error),
+            message: format!("{error:#}"),
+            violations: validation_errors(error)
+                .map(|errors| errors.0.iter().map(ViolationJson::from).collect()),
+        }
+    }
+
+    /// An error that didn't come from a failed action, like a rejected request
+    pub fn other(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            violations: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ViolationJson {
+    field: String,
+    message: String,
+}
+
+impl From<&Violation> for ViolationJson {
+    fn from(violation: &Violation) -> Self {
+        Self {
+            field: violation.field.clone(),
+            message: violation.message.clone(),
+        }
+    }
+}
+
+/// A prediction as written in JSON documents
+#[derive(Serialize)]
+pub struct PredictionJson {
+    pub id: PredictionId,
+    pub broadcaster_id: UserId,
+    pub broadcaster_login: UserName,
+    pub title: String,
+    pub status: PredictionStatus,
+    pub winning_outcome_id: Option<PredictionOutcomeId>,
+    pub outcomes: Vec<PredictionOutcome>,
+    pub prediction_window: i64,
+    pub created_at: Timestamp,
+    pub locked_at: Option<Timestamp>,
+    pub ended_at: Option<Timestamp>,
+}
+
+impl From<&Prediction> for PredictionJson {
+    fn from(prediction: &Prediction) -> Self {
+        Self {
+            id: prediction.id.clone(),
+            broadcaster_id: prediction.broadcaster_id.clone(),
+            broadcaster_login: prediction.broadcaster_login.clone(),
+            title: prediction.title.clone(),
+            status: prediction.status.clone(),
+            winning_outcome_id: prediction.winning_outcome_id.clone(),
+            outcomes: prediction.outcomes.clone(),
+            prediction_window: prediction.prediction_window,
+            created_at: prediction.created_at.clone(),
+            locked_at: prediction.locked_at.clone(),
+            ended_at: prediction.ended_at.clone(),
+        }
+    }
+}
+
+/// The result of an action on a single prediction
+#[derive(Serialize)]
+pub struct PredictionDocument {
+    pub action: &'static str,
+    pub prediction: Option<PredictionJson>,
+    /// Why the action couldn't be journaled, if it wasn't, for `serve` clients that don't see
+    /// the warning on stdout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl PredictionDocument {
+    pub fn new(action: &'static str, prediction: Option<&Prediction>) -> Self {
+        Self {
+            action,
+            prediction: prediction.map(PredictionJson::from),
+            warning: None,
+        }
+    }
+}
+
+/// The active prediction of every profile
+#[derive(Serialize)]
+pub struct ProfilesStatusDocument {
+    pub action: &'static str,
+    pub profiles: Vec<ProfileStatusJson>,
+}
+
+/// The active prediction of a profile, or why it couldn't be fetched
+#[derive(Serialize)]
+pub struct ProfileStatusJson {
+    pub profile: String,
+    pub login: String,
+    pub broadcaster_id: String,
+    pub prediction: Option<PredictionJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorJson>,
+}
+
+impl ProfileStatusJson {
+    pub fn new(
+        name: String,
+        profile: Profile,
+        prediction: anyhow::Result<Option<Prediction>>,
+    ) -> Self {
+        let (prediction, error) = match prediction {
+            Ok(prediction) => (prediction.as_ref().map(PredictionJson::from), None),
+            Err(ref e) => (None, Some(ErrorJson::new(e))),
+        };
+
+        Self {
+            profile: name,
+            login: profile.login,
+            broadcaster_id: profile.broadcaster_id,
+            prediction,
+            error,
+        }
+    }
+}
+
+/// The result of listing predictions
+#[derive(Serialize)]
+pub struct PredictionsDocument {
+    pub action: &'static str,
+    pub predictions: Vec<PredictionJson>,
+}
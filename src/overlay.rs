@@ -0,0 +1,267 @@
+use std::convert::Infallible;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use clap::Args;
+use console::Term;
+use futures_util::Stream;
+use prediction_creator::eventsub::PredictionEvents;
+use prediction_creator::{PredictionService, Totals};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::watch;
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::{PredictionId, PredictionOutcomeId, PredictionStatus};
+
+use crate::events;
+use crate::output::Output;
+
+/// The overlay page, which renders the feed at `/events`
+const PAGE: &str = include_str!("overlay.html");
+
+#[derive(Debug, Args)]
+pub struct OverlayArgs {
+    /// Local address to listen on, add `http://<address>/` as a browser source in OBS
+    #[clap(long, default_value = "127.0.0.1:8790")]
+    listen: SocketAddr,
+
+    /// A CSS file loaded after the built-in styles, to brand the overlay. Read on every page load
+    #[clap(long)]
+    stylesheet: Option<PathBuf>,
+
+    /// How often to fetch the prediction when prediction events are not available, in seconds
+    #[clap(long, default_value = "2")]
+    interval: u64,
+}
+
+#[derive(Clone)]
+struct OverlayState {
+    feed: watch::Receiver<String>,
+    stylesheet: Option<Arc<PathBuf>>,
+}
+
+/// What the overlay shows, sent as the data of every `prediction` event of the feed
+#[derive(Serialize)]
+struct OverlayJson {
+    prediction: Option<OverlayPrediction>,
+}
+
+#[derive(Serialize)]
+struct OverlayPrediction {
+    id: PredictionId,
+    title: String,
+    status: PredictionStatus,
+    /// When viewers can no longer predict, for the countdown
+    locks_at: String,
+    total_points: i64,
+    total_users: i64,
+    outcomes: Vec<OverlayOutcome>,
+    winning_outcome_id: Option<PredictionOutcomeId>,
+}
+
+#[derive(Serialize)]
+struct OverlayOutcome {
+    id: String,
+    title: String,
+    /// `blue` or `pink`
+    color: String,
+    points: i64,
+    users: i64,
+    /// Share of all channel points, from 0 to 100
+    percent: f64,
+}
+
+impl OverlayJson {
+    fn new(prediction: Option<&Prediction>) -> Self {
+        Self {
+            prediction: prediction.map(OverlayPrediction::from),
+        }
+    }
+}
+
+impl From<&Prediction> for OverlayPrediction {
+    fn from(prediction: &Prediction) -> Self {
+        let totals = Totals::of(prediction);
+        let locks_at =
+            prediction.created_at.to_utc() + time::Duration::seconds(prediction.prediction_window);
+
+        Self {
+            id: prediction.id.clone(),
+            title: prediction.title.clone(),
+            status: prediction.status.clone(),
+            locks_at: locks_at
+                .format(&Rfc3339)
+                .unwrap_or_else(|_| prediction.created_at.as_str().to_string()),
+            total_points: totals.channel_points,
+            total_users: totals.users,
+            outcomes: prediction
+                .outcomes
+                .iter()
+                .map(|outcome| {
+                    let points = outcome.channel_points.unwrap_or_default();
+                    OverlayOutcome {
+                        id: outcome.id.clone(),
+                        title: outcome.title.clone(),
+                        // Helix spells the colors in uppercase, EventSub in lowercase
+                        color: outcome.color.to_lowercase(),
+                        points,
+                        users: outcome.users.unwrap_or_default(),
+                        percent: if totals.channel_points > 0 {
+                            points as f64 * 100.0 / totals.channel_points as f64
+                        } else {
+                            0.0
+                        },
+                    }
+                })
+                .collect(),
+            winning_outcome_id: prediction.winning_outcome_id.clone(),
+        }
+    }
+}
+
+/// Serves the overlay until interrupted, following the channel's predictions as they start,
+/// change and end
+///
+/// The page at `/` renders the feed of Server-Sent Events at `/events`. Each event holds
+/// the latest prediction of the channel, including ended ones so the winner stays on screen.
+pub async fn run(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    args: OverlayArgs,
+    events: Option<PredictionEvents>,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .map_err(|e| anyhow::anyhow!("Unable to listen on {}: {e}", args.listen))?;
+    let address = listener.local_addr()?;
+
+    let latest = service.history(Some(1), None).await?.pop();
+    let (feed_tx, feed) = watch::channel(feed_document(latest.as_ref()));
+    let state = OverlayState {
+        feed,
+        stylesheet: args.stylesheet.map(Arc::new),
+    };
+    let app = Router::new()
+        .route("/", get(page))
+        .route("/style.css", get(stylesheet))
+        .route("/events", get(feed_events))
+        .with_state(state);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    writeln!(term, "Overlay at http://{address}/, press Ctrl+C to stop")?;
+    output.emit(&serde_json::json!({
+        "action": "listening",
+        "address": address,
+    }));
+
+    let interval = Duration::from_secs(args.interval.max(1));
+    tokio::select! {
+        result = follow(term, service, latest, events, interval, &feed_tx) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
+
+fn feed_document(prediction: Option<&Prediction>) -> String {
+    serde_json::to_string(&OverlayJson::new(prediction)).expect("the overlay to serialize")
+}
+
+/// Keeps the feed up to date with the latest prediction, from `events` or by polling every `interval`
+///
+/// Failing to fetch the prediction is only reported, the overlay keeps what it last showed.
+async fn follow(
+    term: &mut Term,
+    service: &mut PredictionService,
+    mut latest: Option<Prediction>,
+    mut events: Option<PredictionEvents>,
+    interval: Duration,
+    feed: &watch::Sender<String>,
+) -> anyhow::Result<()> {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        let polling = events.is_none();
+        let refetch = tokio::select! {
+            _ = ticker.tick(), if polling => true,
+            event = events::next(&mut events) => match event {
+                Ok(event) => match latest {
+                    Some(ref mut prediction) if prediction.id == event.id => {
+                        event.apply(prediction);
+                        false
+                    }
+                    // A new prediction started
+                    _ => true,
+                },
+                Err(e) => {
+                    events::warn(term, &e)?;
+                    events = None;
+                    true
+                }
+            },
+        };
+
+        if refetch {
+            match service.history(Some(1), None).await {
+                Ok(mut predictions) => latest = predictions.pop(),
+                Err(e) => {
+                    writeln!(term, "Unable to fetch the prediction: {e}")?;
+                    continue;
+                }
+            }
+        }
+
+        feed.send_if_modified(|document| {
+            let updated = feed_document(latest.as_ref());
+            let modified = *document != updated;
+            *document = updated;
+            modified
+        });
+    }
+}
+
+async fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+async fn stylesheet(State(state): State<OverlayState>) -> Response {
+    let css = match state.stylesheet {
+        Some(ref path) => match tokio::fs::read_to_string(path.as_path()).await {
+            Ok(css) => css,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Unable to read {}: {e}", path.display()),
+                )
+                    .into_response()
+            }
+        },
+        None => String::new(),
+    };
+
+    ([(header::CONTENT_TYPE, "text/css")], css).into_response()
+}
+
+/// Sends the current document right away, then every change to it
+async fn feed_events(
+    State(state): State<OverlayState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut feed = state.feed;
+    feed.mark_changed();
+
+    let stream = futures_util::stream::unfold(feed, |mut feed| async move {
+        feed.changed().await.ok()?;
+        let data = feed.borrow_and_update().clone();
+        Some((Ok(Event::default().event("prediction").data(data)), feed))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
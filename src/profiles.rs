@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config, Error, Result};
+
+/// A channel managed with a token of its own, stored by `login --profile <name>`
+///
+/// ```toml
+/// [main]
+/// login = "my_channel"
+/// broadcaster_id = "12345"
+///
+/// [costream]
+/// login = "a_friend"
+/// broadcaster_id = "67890"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub login: String,
+    pub broadcaster_id: String,
+}
+
+/// The default location of the profiles file
+pub fn default_path() -> Result<PathBuf> {
+    Ok(config::config_dir()?.join("profiles.toml"))
+}
+
+/// The file the token of the profile called `name` is stored in
+pub fn token_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::InvalidArguments(format!(
+            "Invalid profile name {name:?}, use only letters, digits, dashes and underscores"
+        )));
+    }
+
+    Ok(config::config_dir()?
+        .join("profiles")
+        .join(format!("{name}.json")))
+}
+
+/// Loads every profile from the profiles file at `path`, which may not exist yet
+pub fn load(path: &Path) -> Result<BTreeMap<String, Profile>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => {
+            return Err(Error::Storage(format!(
+                "Unable to read profiles from {}: {e}",
+                path.display()
+            )))
+        }
+    };
+
+    let profiles = toml::from_str(&contents).map_err(|e| {
+        Error::Storage(format!(
+            "Unable to parse profiles in {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    Ok(profiles)
+}
+
+/// Loads the profile called `name` from the profiles file at `path`
+pub fn find(path: &Path, name: &str) -> Result<Profile> {
+    let mut profiles = load(path)?;
+
+    profiles.remove(name).ok_or_else(|| {
+        let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        Error::UnknownProfile {
+            name: name.to_string(),
+            path: path.display().to_string(),
+            available: available.join(", "),
+        }
+    })
+}
+
+/// Adds the profile called `name` to the profiles file at `path`, replacing any profile of that name
+pub fn save(path: &Path, name: &str, profile: Profile) -> Result<()> {
+    let mut profiles = load(path)?;
+    profiles.insert(name.to_string(), profile);
+
+    let contents = toml::to_string(&profiles)
+        .map_err(|e| Error::Storage(format!("Unable to serialize profiles: {e}")))?;
+    let write = || -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)
+    };
+
+    write().map_err(|e| {
+        Error::Storage(format!(
+            "Unable to write profiles to {}: {e}",
+            path.display()
+        ))
+    })
+}
@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use twitch_api::types::PredictionId;
+
+use crate::validation;
+use crate::{Error, Result};
+
+/// A prediction waiting in the queue file
+///
+/// ```toml
+/// [[prediction]]
+/// title = "Who wins round 1?"
+/// outcomes = ["Team Red", "Team Blue"]
+/// prediction_window = 120
+///
+/// [[prediction]]
+/// title = "Who wins round 2?"
+/// outcomes = ["Team Red", "Team Blue"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueuedPrediction {
+    pub title: String,
+    pub outcomes: Vec<String>,
+    #[serde(default = "validation::default_prediction_window")]
+    pub prediction_window: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QueueFile {
+    #[serde(default)]
+    prediction: Vec<QueuedPrediction>,
+}
+
+/// How far a queue got, stored next to the queue file after every step
+#[derive(Debug, Serialize, Deserialize)]
+struct Progress {
+    /// Positions in the queue file of the predictions still to run, in the order they run in
+    remaining: Vec<usize>,
+    /// The prediction started for the first remaining entry, once it was started
+    running: Option<PredictionId>,
+}
+
+/// Predictions run one after the other, in the order of the queue file unless reordered
+///
+/// Every change is written to the progress file right away, so a run that stopped
+/// for whatever reason is resumed by loading the queue again.
+#[derive(Debug)]
+pub struct Queue {
+    predictions: Vec<QueuedPrediction>,
+    progress: Progress,
+    progress_path: PathBuf,
+}
+
+impl Queue {
+    /// Loads the queue file at `path`, resuming from its progress file unless `restart` is set
+    ///
+    /// Every queued prediction is validated, so mistakes surface before the first one starts.
+    pub fn load(path: &Path, restart: bool) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            Error::Storage(format!("Unable to read queue from {}: {e}", path.display()))
+        })?;
+        let file: QueueFile = toml::from_str(&contents).map_err(|e| {
+            Error::Storage(format!("Unable to parse queue in {}: {e}", path.display()))
+        })?;
+
+        for (i, prediction) in file.prediction.iter().enumerate() {
+            validation::validate_prediction(
+                &prediction.title,
+                &prediction.outcomes,
+                prediction.prediction_window,
+            )
+            .map_err(|errors| Error::InvalidQueued {
+                position: i + 1,
+                path: path.display().to_string(),
+                errors,
+            })?;
+        }
+
+        let progress_path = Self::progress_path(path);
+        let progress = match fs::read_to_string(&progress_path) {
+            Ok(_) if restart => None,
+            Ok(contents) => {
+                let progress: Progress = serde_json::from_str(&contents).map_err(|e| {
+                    Error::Storage(format!(
+                        "Unable to parse queue progress in {}: {e}",
+                        progress_path.display()
+                    ))
+                })?;
+                if progress
+                    .remaining
+                    .iter()
+                    .any(|&i| i >= file.prediction.len())
+                {
+                    return Err(Error::Queue(format!(
+                        "{} no longer matches {}, pass --restart to start the queue over",
+                        progress_path.display(),
+                        path.display()
+                    )));
+                }
+                Some(progress)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(Error::Storage(format!(
+                    "Unable to read queue progress from {}: {e}",
+                    progress_path.display()
+                )))
+            }
+        };
+
+        let queue = Self {
+            progress: progress.unwrap_or_else(|| Progress {
+                remaining: (0..file.prediction.len()).collect(),
+                running: None,
+            }),
+            predictions: file.prediction,
+            progress_path,
+        };
+        queue.save()?;
+
+        Ok(queue)
+    }
+
+    /// Where the progress through the queue file at `path` is stored, e.g. `finals.progress.json` for `finals.toml`
+    pub fn progress_path(path: &Path) -> PathBuf {
+        path.with_extension("progress.json")
+    }
+
+    /// The prediction that was started and hasn't ended yet, as far as the queue knows
+    pub fn running(&self) -> Option<(&QueuedPrediction, &PredictionId)> {
+        let id = self.progress.running.as_ref()?;
+        let &position = self.progress.remaining.first()?;
+        Some((&self.predictions[position], id))
+    }
+
+    /// The predictions that haven't started yet, in the order they run in
+    pub fn upcoming(&self) -> impl Iterator<Item = &QueuedPrediction> {
+        let started = usize::from(self.progress.running.is_some());
+        self.progress
+            .remaining
+            .iter()
+            .skip(started)
+            .map(|&position| &self.predictions[position])
+    }
+
+    /// The prediction that starts once the running one ends
+    pub fn next_up(&self) -> Option<&QueuedPrediction> {
+        self.upcoming().next()
+    }
+
+    /// Whether every prediction has run or was skipped
+    pub fn is_finished(&self) -> bool {
+        self.progress.remaining.is_empty()
+    }
+
+    /// Records that the prediction next up was started as `id`
+    pub fn start(&mut self, id: PredictionId) -> Result<()> {
+        if self.progress.running.is_some() {
+            return Err(Error::Queue(
+                "A queued prediction is already running".to_string(),
+            ));
+        }
+        if self.progress.remaining.is_empty() {
+            return Err(Error::Queue("The queue is finished".to_string()));
+        }
+
+        self.progress.running = Some(id);
+        self.save()
+    }
+
+    /// Records that the running prediction ended, making room for the one next up
+    pub fn finish(&mut self) -> Result<()> {
+        if self.progress.running.take().is_some() {
+            self.progress.remaining.remove(0);
+        }
+
+        self.save()
+    }
+
+    /// Drops the prediction next up from the queue, returning it
+    pub fn skip(&mut self) -> Result<Option<QueuedPrediction>> {
+        let started = usize::from(self.progress.running.is_some());
+        if self.progress.remaining.len() <= started {
+            return Ok(None);
+        }
+
+        let position = self.progress.remaining.remove(started);
+        self.save()?;
+
+        Ok(Some(self.predictions[position].clone()))
+    }
+
+    /// Puts the upcoming predictions in a new order
+    ///
+    /// `order` lists positions in [`Queue::upcoming`], e.g. `[1, 0]` swaps the next two predictions.
+    pub fn reorder(&mut self, order: &[usize]) -> Result<()> {
+        let started = usize::from(self.progress.running.is_some());
+        let upcoming = &self.progress.remaining[started..];
+
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        if sorted != (0..upcoming.len()).collect::<Vec<_>>() {
+            return Err(Error::InvalidArguments(
+                "The new order must list every upcoming prediction exactly once".to_string(),
+            ));
+        }
+
+        let reordered: Vec<usize> = order.iter().map(|&i| upcoming[i]).collect();
+        self.progress.remaining.truncate(started);
+        self.progress.remaining.extend(reordered);
+
+        self.save()
+    }
+
+    /// Writes the progress to a temporary file first, so a crash never leaves half of it behind
+    fn save(&self) -> Result<()> {
+        let temporary = self.progress_path.with_extension("json.tmp");
+        let write = || -> std::io::Result<()> {
+            fs::write(&temporary, serde_json::to_string_pretty(&self.progress)?)?;
+            fs::rename(&temporary, &self.progress_path)
+        };
+
+        write().map_err(|e| {
+            Error::Storage(format!(
+                "Unable to write queue progress to {}: {e}",
+                self.progress_path.display()
+            ))
+        })
+    }
+}
@@ -0,0 +1,295 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Args;
+use console::Term;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Select, Sort};
+use prediction_creator::eventsub::PredictionEvents;
+use prediction_creator::queue::Queue;
+use prediction_creator::PredictionService;
+use twitch_api::helix::predictions::Prediction;
+use twitch_api::types::{PredictionOutcome, PredictionStatus};
+
+use crate::events;
+use crate::output::{Output, PredictionDocument};
+use crate::{print_end_prediction, print_prediction};
+
+/// How often the running prediction is checked on when there are no events to follow it with
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Args)]
+pub struct QueueArgs {
+    /// The queue file, listing the predictions to run as `[[prediction]]` tables with
+    /// a `title`, `outcomes` and optionally a `prediction_window`
+    file: PathBuf,
+
+    /// Start over with the first prediction, instead of resuming where the last run stopped
+    #[clap(long)]
+    restart: bool,
+}
+
+/// An entry picked from the queue menu
+#[derive(Clone)]
+enum QueueAction {
+    Resolve(PredictionOutcome),
+    Lock,
+    Cancel,
+    Skip,
+    Reorder,
+    Quit,
+}
+
+/// Runs the predictions of the queue file one after the other
+///
+/// When someone is at the terminal, each prediction is managed from a menu that also
+/// shows what is next up and allows skipping or reordering what is left. Otherwise each
+/// prediction runs until it is resolved or canceled elsewhere, e.g. by a moderator.
+pub async fn run(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    args: QueueArgs,
+    mut events: Option<PredictionEvents>,
+) -> anyhow::Result<()> {
+    let mut queue = Queue::load(&args.file, args.restart)?;
+    let interactive = console::user_attended_stderr();
+
+    while let Some(prediction) = start_next(term, output, service, &mut queue).await? {
+        if interactive {
+            if !manage(term, output, service, &mut queue, prediction, &mut events).await? {
+                return Ok(());
+            }
+        } else {
+            follow(term, output, service, prediction, &mut events).await?;
+        }
+        queue.finish()?;
+    }
+
+    writeln!(term, "The queue is finished")?;
+    output.emit(&PredictionDocument::new("queue_finished", None));
+
+    Ok(())
+}
+
+/// Picks the running prediction back up, or starts the one next up
+///
+/// Returns `None` once the queue is finished.
+async fn start_next(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    queue: &mut Queue,
+) -> anyhow::Result<Option<Prediction>> {
+    while let Some((queued, id)) = queue.running() {
+        let title = queued.title.clone();
+        let prediction = service.get(id).await?;
+        if matches!(
+            prediction.status,
+            PredictionStatus::Active | PredictionStatus::Locked
+        ) {
+            writeln!(term, "Resuming {}", console::style(&title).bold())?;
+            print_prediction(term, &prediction)?;
+            output.emit(&PredictionDocument::new("resumed", Some(&prediction)));
+            return Ok(Some(prediction));
+        }
+
+        writeln!(term, "{title} ended while the queue wasn't running")?;
+        queue.finish()?;
+    }
+
+    let Some(next) = queue.next_up().cloned() else {
+        return Ok(None);
+    };
+
+    // Stopping right after starting a prediction leaves it running without the queue knowing
+    let (prediction, action) = match service.current().await? {
+        Some(current) if current.title == next.title => (current, "found_active"),
+        _ => {
+            let created = output.journaled(
+                service
+                    .create(&next.title, &next.outcomes, next.prediction_window)
+                    .await?,
+            );
+            (created, "created")
+        }
+    };
+    queue.start(prediction.id.clone())?;
+
+    print_prediction(term, &prediction)?;
+    output.emit(&PredictionDocument::new(action, Some(&prediction)));
+
+    Ok(Some(prediction))
+}
+
+/// Shows the menu for the running prediction until it ends
+///
+/// Returns `false` if the queue was left, with the prediction still running.
+async fn manage(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    queue: &mut Queue,
+    mut prediction: Prediction,
+    events: &mut Option<PredictionEvents>,
+) -> anyhow::Result<bool> {
+    loop {
+        let next_up = queue.next_up().map(|next| next.title.clone());
+        match next_up {
+            Some(ref title) => writeln!(term, "Next up: {}", console::style(title).bold())?,
+            None => writeln!(term, "This is the last prediction in the queue")?,
+        }
+
+        let outcomes = prediction.outcomes.clone();
+        let locked = prediction.status == PredictionStatus::Locked;
+        let reorderable = queue.upcoming().count() > 1;
+        let selected = events::prompt(term, &mut prediction, events, move || {
+            select_action(&outcomes, locked, next_up, reorderable)
+        })
+        .await?;
+
+        let Some(action) = selected else {
+            print_end_prediction(term, &prediction, "ended")?;
+            output.emit(&PredictionDocument::new(
+                "ended_elsewhere",
+                Some(&prediction),
+            ));
+            return Ok(true);
+        };
+
+        match action {
+            QueueAction::Resolve(outcome) => {
+                let ended = output.journaled(service.resolve(&prediction.id, &outcome.id).await?);
+                print_end_prediction(term, &ended, "resolved")?;
+                output.emit(&PredictionDocument::new("resolved", Some(&ended)));
+                return Ok(true);
+            }
+            QueueAction::Cancel => {
+                let ended = output.journaled(service.cancel(&prediction.id).await?);
+                print_end_prediction(term, &ended, "canceled")?;
+                output.emit(&PredictionDocument::new("canceled", Some(&ended)));
+                return Ok(true);
+            }
+            QueueAction::Lock if prediction.status == PredictionStatus::Locked => {
+                writeln!(term, "The prediction was locked in the meantime")?;
+            }
+            QueueAction::Lock => {
+                prediction = output.journaled(service.lock(&prediction.id).await?);
+                print_end_prediction(term, &prediction, "locked")?;
+                output.emit(&PredictionDocument::new("locked", Some(&prediction)));
+            }
+            QueueAction::Skip => {
+                if let Some(skipped) = queue.skip()? {
+                    writeln!(term, "Skipped {}", skipped.title)?;
+                }
+            }
+            QueueAction::Reorder => {
+                let titles: Vec<String> = queue.upcoming().map(|next| next.title.clone()).collect();
+                let order = Sort::with_theme(&ColorfulTheme::default())
+                    .with_prompt("put the upcoming predictions in the order they should run in")
+                    .items(&titles)
+                    .interact()?;
+                queue.reorder(&order)?;
+            }
+            QueueAction::Quit => {
+                writeln!(
+                    term,
+                    "Leaving {} running, run the queue again to pick it back up",
+                    prediction.title
+                )?;
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Prompts for what to do with the running prediction or the rest of the queue
+fn select_action(
+    outcomes: &[PredictionOutcome],
+    locked: bool,
+    next_up: Option<String>,
+    reorderable: bool,
+) -> anyhow::Result<QueueAction> {
+    let mut choices: Vec<(String, QueueAction)> = outcomes
+        .iter()
+        .enumerate()
+        .map(|(i, outcome)| {
+            (
+                format!("[{}] {}", i + 1, outcome.title),
+                QueueAction::Resolve(outcome.clone()),
+            )
+        })
+        .collect();
+
+    if !locked {
+        choices.push(("LOCK".to_string(), QueueAction::Lock));
+    }
+    choices.push(("CANCEL".to_string(), QueueAction::Cancel));
+    if let Some(next_up) = next_up {
+        choices.push((format!("SKIP {next_up}"), QueueAction::Skip));
+    }
+    if reorderable {
+        choices.push(("REORDER QUEUE".to_string(), QueueAction::Reorder));
+    }
+    choices.push((
+        "QUIT, leaving the prediction running".to_string(),
+        QueueAction::Quit,
+    ));
+
+    let labels: Vec<&str> = choices.iter().map(|(label, _)| label.as_str()).collect();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("your selection please")
+        .default(0)
+        .items(&labels)
+        .interact()?;
+
+    Ok(choices.swap_remove(selection).1)
+}
+
+/// Waits for the prediction to be resolved or canceled elsewhere
+async fn follow(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    mut prediction: Prediction,
+    events: &mut Option<PredictionEvents>,
+) -> anyhow::Result<()> {
+    writeln!(
+        term,
+        "Waiting for {} to be resolved or canceled",
+        prediction.title
+    )?;
+
+    loop {
+        let polling = events.is_none();
+        tokio::select! {
+            event = events::next(events) => match event {
+                Ok(event) => {
+                    event.apply(&mut prediction);
+                    if event.ends(&prediction) {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    events::warn(term, &e)?;
+                    *events = None;
+                }
+            },
+            _ = tokio::time::sleep(POLL_INTERVAL), if polling => {
+                prediction = service.get(&prediction.id).await?;
+                if matches!(prediction.status, PredictionStatus::Resolved | PredictionStatus::Canceled) {
+                    break;
+                }
+            }
+        }
+    }
+
+    print_end_prediction(term, &prediction, "ended")?;
+    output.emit(&PredictionDocument::new(
+        "ended_elsewhere",
+        Some(&prediction),
+    ));
+
+    Ok(())
+}
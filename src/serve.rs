@@ -0,0 +1,247 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Args;
+use console::Term;
+use prediction_creator::validation;
+use prediction_creator::{find_outcome, Error, PredictionService, Recorded};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use twitch_api::types::PredictionStatus;
+
+use crate::output::{self, ErrorJson, Output, PredictionDocument};
+
+/// Header every request must carry the shared secret in
+pub const SECRET_HEADER: &str = "x-prediction-secret";
+
+#[derive(Debug, Args)]
+pub struct ServeArgs {
+    /// Local address to listen on. Port 0 picks a free port
+    #[clap(long, default_value = "127.0.0.1:8789")]
+    listen: SocketAddr,
+
+    /// Secret every request must send in the `X-Prediction-Secret` header
+    #[clap(long, env = "PREDICTION_CREATOR_SECRET", hide_env_values = true)]
+    secret: String,
+}
+
+#[derive(Clone)]
+struct ApiState {
+    /// Requests are handled one at a time, so e.g. a lock and a resolve never race each other
+    service: Arc<Mutex<PredictionService>>,
+    output: Output,
+    secret: Arc<str>,
+}
+
+/// The body of `POST /predictions`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewPrediction {
+    title: String,
+    outcomes: Vec<String>,
+    #[serde(default = "validation::default_prediction_window")]
+    prediction_window: i64,
+}
+
+/// The body of `POST /predictions/current/resolve`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Resolution {
+    /// The winning outcome, as accepted by `resolve --winner`
+    outcome: String,
+}
+
+/// A failed request, answered with the same error document the CLI writes in JSON mode
+struct ApiError(StatusCode, ErrorJson);
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        let status = match error.downcast_ref::<Error>() {
+            Some(Error::NoActivePrediction | Error::PredictionNotFound(_)) => StatusCode::NOT_FOUND,
+            Some(Error::PredictionAlreadyActive(_) | Error::AlreadyLocked(_)) => {
+                StatusCode::CONFLICT
+            }
+            Some(Error::UnknownOutcome { .. } | Error::AmbiguousOutcome { .. }) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            Some(Error::RateLimited) => StatusCode::TOO_MANY_REQUESTS,
+            Some(Error::Network(_)) => StatusCode::BAD_GATEWAY,
+            _ if output::exit_code(&error) == 2 => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        Self(status, ErrorJson::new(&error))
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        anyhow::Error::from(error).into()
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.1 });
+        (self.0, Json(body)).into_response()
+    }
+}
+
+type ApiResult = Result<Json<PredictionDocument>, ApiError>;
+
+/// Serves the REST API until interrupted
+///
+/// ```text
+/// POST /predictions                    {"title", "outcomes", "prediction_window"}
+/// GET  /predictions/current
+/// POST /predictions/current/lock
+/// POST /predictions/current/resolve    {"outcome"}
+/// POST /predictions/current/cancel
+/// ```
+///
+/// Every response is a JSON document like the ones written by `--output json`.
+pub async fn run(
+    term: &mut Term,
+    output: Output,
+    service: PredictionService,
+    args: ServeArgs,
+) -> anyhow::Result<()> {
+    if args.secret.is_empty() {
+        anyhow::bail!("The secret must not be empty");
+    }
+
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .map_err(|e| anyhow::anyhow!("Unable to listen on {}: {e}", args.listen))?;
+    let address = listener.local_addr()?;
+
+    let state = ApiState {
+        service: Arc::new(Mutex::new(service)),
+        output,
+        secret: args.secret.into(),
+    };
+    let app = Router::new()
+        .route("/predictions", post(create))
+        .route("/predictions/current", get(current))
+        .route("/predictions/current/lock", post(lock))
+        .route("/predictions/current/resolve", post(resolve))
+        .route("/predictions/current/cancel", post(cancel))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_secret,
+        ))
+        .with_state(state);
+
+    writeln!(term, "Listening on http://{address}, press Ctrl+C to stop")?;
+    output.emit(&serde_json::json!({
+        "action": "listening",
+        "address": address,
+    }));
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn require_secret(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(SECRET_HEADER)
+        .is_some_and(|secret| constant_time_eq(secret.as_bytes(), state.secret.as_bytes()));
+    if !authorized {
+        return ApiError(
+            StatusCode::UNAUTHORIZED,
+            ErrorJson::other(
+                "unauthorized",
+                "The X-Prediction-Secret header is missing or wrong",
+            ),
+        )
+        .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Compares without returning early, so the time taken doesn't tell how much of the secret was guessed
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Tells whoever runs the server what was done, and answers the request with it
+///
+/// Twitch already accepted the action, so a journal that couldn't be written is only a warning.
+/// It is reported on its own like every other command does, and in the response to the request.
+fn report(state: &ApiState, action: &'static str, recorded: Recorded) -> ApiResult {
+    let warning = recorded.journal_error.as_ref().map(ToString::to_string);
+    let prediction = state.output.journaled(recorded);
+    let _ = writeln!(
+        state.output.term(),
+        "{} {}",
+        console::style(action).bold(),
+        prediction.title
+    );
+    state
+        .output
+        .emit(&PredictionDocument::new(action, Some(&prediction)));
+
+    Ok(Json(PredictionDocument {
+        warning,
+        ..PredictionDocument::new(action, Some(&prediction))
+    }))
+}
+
+async fn create(State(state): State<ApiState>, Json(body): Json<NewPrediction>) -> ApiResult {
+    let recorded = state
+        .service
+        .lock()
+        .await
+        .create(&body.title, &body.outcomes, body.prediction_window)
+        .await?;
+
+    report(&state, "created", recorded)
+}
+
+async fn current(State(state): State<ApiState>) -> ApiResult {
+    let prediction = state.service.lock().await.require_current().await?;
+
+    Ok(Json(PredictionDocument::new("status", Some(&prediction))))
+}
+
+async fn lock(State(state): State<ApiState>) -> ApiResult {
+    let mut service = state.service.lock().await;
+    let prediction = service.require_current().await?;
+    if prediction.status == PredictionStatus::Locked {
+        return Err(Error::AlreadyLocked(prediction.title).into());
+    }
+    let recorded = service.lock(&prediction.id).await?;
+
+    report(&state, "locked", recorded)
+}
+
+async fn resolve(State(state): State<ApiState>, Json(body): Json<Resolution>) -> ApiResult {
+    let mut service = state.service.lock().await;
+    let prediction = service.require_current().await?;
+    let outcome = find_outcome(&prediction.outcomes, &body.outcome)?;
+    let recorded = service.resolve(&prediction.id, &outcome.id).await?;
+
+    report(&state, "resolved", recorded)
+}
+
+async fn cancel(State(state): State<ApiState>) -> ApiResult {
+    let mut service = state.service.lock().await;
+    let prediction = service.require_current().await?;
+    let recorded = service.cancel(&prediction.id).await?;
+
+    report(&state, "canceled", recorded)
+}
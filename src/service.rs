@@ -0,0 +1,477 @@
+use std::path::PathBuf;
+
+use time::OffsetDateTime;
+use twitch_api::helix::predictions::end_prediction::EndPrediction;
+use twitch_api::helix::predictions::{
+    create_prediction, end_prediction, get_predictions, Prediction,
+};
+use twitch_api::helix::{HelixClient, Paginated};
+use twitch_api::twitch_oauth2::UserToken;
+use twitch_api::types::{PredictionIdRef, PredictionOutcome, PredictionStatus, UserId};
+
+use crate::eventsub::PredictionEvents;
+use crate::journal::{Journal, JournalAction};
+use crate::{auth, validation, Error, Result};
+
+async fn start_prediction(
+    client: &HelixClient<'_, reqwest::Client>,
+    token: &UserToken,
+    channel_id: &UserId,
+    title: &str,
+    options: &[String],
+    prediction_window: i64,
+) -> Result<create_prediction::CreatePredictionResponse> {
+    let request = create_prediction::CreatePredictionRequest::new();
+    let outcomes = new_outcomes(options);
+    let body = create_prediction::CreatePredictionBody::new(
+        channel_id,
+        title,
+        &outcomes,
+        prediction_window,
+    );
+
+    let response = client.req_post(request, body, token).await?.data;
+
+    Ok(response)
+}
+
+/// The outcomes of a new prediction, as sent to Helix
+pub(crate) fn new_outcomes(options: &[String]) -> Vec<create_prediction::NewPredictionOutcome<'_>> {
+    options
+        .iter()
+        .map(create_prediction::NewPredictionOutcome::new)
+        .collect()
+}
+
+/// The body of a request to lock, resolve or cancel a prediction
+pub(crate) fn end_body<'a>(
+    channel_id: &'a UserId,
+    prediction_id: &'a PredictionIdRef,
+    new_status: PredictionStatus,
+    winning_outcome_id: Option<String>,
+) -> end_prediction::EndPredictionBody<'a> {
+    let mut body = end_prediction::EndPredictionBody::new(channel_id, prediction_id, new_status);
+    if let Some(winning_outcome_id) = winning_outcome_id {
+        body.winning_outcome_id = Some(std::borrow::Cow::Owned(winning_outcome_id.into()));
+    }
+
+    body
+}
+
+async fn get_last_prediction(
+    client: &HelixClient<'_, reqwest::Client>,
+    token: &UserToken,
+    channel_id: &UserId,
+) -> Result<Option<Prediction>> {
+    let mut request = get_predictions::GetPredictionsRequest::broadcaster_id(channel_id);
+    request.first = Some(1);
+
+    let mut response = client.req_get(request, token).await?.data;
+
+    if let Some(last_prediction) = response.pop() {
+        if last_prediction.status == PredictionStatus::Active
+            || last_prediction.status == PredictionStatus::Locked
+        {
+            Ok(Some(last_prediction))
+        } else {
+            Ok(None)
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+async fn get_prediction(
+    client: &HelixClient<'_, reqwest::Client>,
+    token: &UserToken,
+    channel_id: &UserId,
+    prediction_id: &PredictionIdRef,
+) -> Result<Prediction> {
+    let request = get_predictions::GetPredictionsRequest::broadcaster_id(channel_id)
+        .ids(vec![prediction_id.to_owned()]);
+
+    let mut response = client.req_get(request, token).await?.data;
+
+    response
+        .pop()
+        .ok_or_else(|| Error::PredictionNotFound(prediction_id.to_owned()))
+}
+
+/// How many predictions to request per page, the most Get Predictions allows
+const PAGE_SIZE: usize = 25;
+
+/// Follows the cursor through past predictions, newest first, until `limit` predictions
+/// were found or the predictions get older than `since`
+async fn list_predictions(
+    client: &HelixClient<'_, reqwest::Client>,
+    token: &UserToken,
+    channel_id: &UserId,
+    limit: Option<usize>,
+    since: Option<OffsetDateTime>,
+) -> Result<Vec<Prediction>> {
+    let mut request = get_predictions::GetPredictionsRequest::broadcaster_id(channel_id);
+    request.first = Some(limit.map_or(PAGE_SIZE, |limit| limit.clamp(1, PAGE_SIZE)));
+
+    let mut predictions = Vec::new();
+    loop {
+        let response = client.req_get(request.clone(), token).await?;
+
+        for prediction in response.data {
+            if limit.is_some_and(|limit| predictions.len() >= limit)
+                || since.is_some_and(|since| prediction.created_at.to_utc() < since)
+            {
+                return Ok(predictions);
+            }
+            predictions.push(prediction);
+        }
+
+        // Don't fetch another page only to find the limit was already reached
+        if limit.is_some_and(|limit| predictions.len() >= limit) {
+            return Ok(predictions);
+        }
+
+        match response.pagination {
+            Some(cursor) => request.set_pagination(Some(cursor)),
+            None => return Ok(predictions),
+        }
+    }
+}
+
+async fn end_prediction<'a>(
+    client: &'a HelixClient<'a, reqwest::Client>,
+    token: &'a UserToken,
+    channel_id: &'a UserId,
+    prediction_id: &'a PredictionIdRef,
+    new_status: PredictionStatus,
+    winning_outcome_id: Option<String>,
+) -> Result<Prediction> {
+    let request = end_prediction::EndPredictionRequest::new();
+    let body = end_body(channel_id, prediction_id, new_status, winning_outcome_id);
+
+    let response = client.req_patch(request, body, token).await?.data;
+
+    match response {
+        EndPrediction::Success(prediction) => Ok(prediction),
+        EndPrediction::MissingQuery => Err(Error::BadRequest),
+        EndPrediction::AuthFailed => Err(Error::InvalidToken(
+            "Twitch rejected the token while ending the prediction".to_string(),
+        )),
+        unknown => Err(Error::UnexpectedResponse(format!("{unknown:?}"))),
+    }
+}
+
+/// The channel points and users across all outcomes of a prediction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub channel_points: i64,
+    pub users: i64,
+}
+
+impl Totals {
+    /// Sums up the outcomes of `prediction`, counting outcomes nobody predicted on yet as 0
+    pub fn of(prediction: &Prediction) -> Self {
+        prediction
+            .outcomes
+            .iter()
+            .fold(Self::default(), |totals, outcome| Self {
+                channel_points: totals.channel_points + outcome.channel_points.unwrap_or_default(),
+                users: totals.users + outcome.users.unwrap_or_default(),
+            })
+    }
+}
+
+/// Finds the outcome described by `winner`, which is either an outcome id,
+/// a 1-based outcome number or a case-insensitive outcome title
+pub fn find_outcome<'a>(
+    outcomes: &'a [PredictionOutcome],
+    winner: &str,
+) -> Result<&'a PredictionOutcome> {
+    let winner = winner.trim();
+    let lowercase = winner.to_lowercase();
+
+    if let Some(outcome) = outcomes.iter().find(|outcome| outcome.id == winner) {
+        return Ok(outcome);
+    }
+
+    let by_index = winner
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .and_then(|i| outcomes.get(i));
+
+    let by_title: Vec<&PredictionOutcome> = outcomes
+        .iter()
+        .filter(|outcome| outcome.title.trim().to_lowercase() == lowercase)
+        .collect();
+
+    match (by_index, by_title.as_slice()) {
+        (Some(outcome), []) => Ok(outcome),
+        (None, [outcome]) => Ok(outcome),
+        (Some(indexed), [titled]) if indexed.id == titled.id => Ok(indexed),
+        (Some(indexed), [titled]) => Err(Error::AmbiguousOutcome {
+            winner: winner.to_string(),
+            reason: format!(
+                "it is both the number of outcome {:?} and the title of outcome {:?}",
+                indexed.title, titled.title
+            ),
+        }),
+        (_, []) => {
+            let available: Vec<String> = outcomes
+                .iter()
+                .enumerate()
+                .map(|(i, outcome)| format!("[{}] {}", i + 1, outcome.title))
+                .collect();
+            Err(Error::UnknownOutcome {
+                winner: winner.to_string(),
+                available: available.join(", "),
+            })
+        }
+        (_, titled) => Err(Error::AmbiguousOutcome {
+            winner: winner.to_string(),
+            reason: format!(
+                "{} outcomes have that title, use the outcome number or id instead",
+                titled.len()
+            ),
+        }),
+    }
+}
+
+/// A prediction Twitch just created, locked, resolved or canceled
+///
+/// The journal is written after Twitch accepted the action, so failing to write it doesn't
+/// undo anything. Instead of failing the action, the error is handed back in `journal_error`.
+#[derive(Debug)]
+pub struct Recorded {
+    pub prediction: Prediction,
+    pub journal_error: Option<Error>,
+}
+
+/// Manages the predictions of a single channel
+///
+/// The token is refreshed before each request if it has expired, so a service can be
+/// kept around for as long as the stream runs.
+pub struct PredictionService {
+    client: HelixClient<'static, reqwest::Client>,
+    token: UserToken,
+    broadcaster_id: UserId,
+    journal: Option<Journal>,
+    token_path: Option<PathBuf>,
+}
+
+impl PredictionService {
+    /// Creates a service managing the predictions of the channel the token belongs to
+    pub fn new(client: HelixClient<'static, reqwest::Client>, token: UserToken) -> Self {
+        let broadcaster_id = token.user_id.clone();
+
+        Self {
+            client,
+            token,
+            broadcaster_id,
+            journal: None,
+            token_path: None,
+        }
+    }
+
+    /// Records every prediction this service creates, locks, resolves or cancels in `journal`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Stores the token at `path` when it is refreshed, instead of the file written by `login`
+    pub fn with_token_path(mut self, path: PathBuf) -> Self {
+        self.token_path = Some(path);
+        self
+    }
+
+    pub fn client(&self) -> &HelixClient<'static, reqwest::Client> {
+        &self.client
+    }
+
+    pub fn token(&self) -> &UserToken {
+        &self.token
+    }
+
+    pub fn broadcaster_id(&self) -> &UserId {
+        &self.broadcaster_id
+    }
+
+    /// The token, refreshed first if it has expired, for connections that don't go through
+    /// Helix, like chat
+    pub async fn fresh_token(&mut self) -> Result<&UserToken> {
+        self.refresh().await?;
+        Ok(&self.token)
+    }
+
+    /// Refreshes the token if it has expired, e.g. while waiting on an interactive prompt
+    async fn refresh(&mut self) -> Result<()> {
+        auth::refresh_if_expired(&self.client, &mut self.token, self.token_path.as_deref()).await
+    }
+
+    fn record(&self, action: JournalAction, prediction: Prediction) -> Recorded {
+        let journal_error = match self.journal {
+            Some(ref journal) => journal.record(action, &prediction).err(),
+            None => None,
+        };
+
+        Recorded {
+            prediction,
+            journal_error,
+        }
+    }
+
+    /// Validates and starts a new prediction
+    pub async fn create(
+        &mut self,
+        title: &str,
+        outcomes: &[String],
+        prediction_window: i64,
+    ) -> Result<Recorded> {
+        validation::validate_prediction(title, outcomes, prediction_window)?;
+        self.refresh().await?;
+
+        let prediction = start_prediction(
+            &self.client,
+            &self.token,
+            &self.broadcaster_id,
+            title,
+            outcomes,
+            prediction_window,
+        )
+        .await?;
+
+        Ok(self.record(JournalAction::Created, prediction))
+    }
+
+    /// Makes sure the predictions of the channel called `login` can be managed with our token
+    ///
+    /// Helix only accepts a broadcaster's own token for their predictions, so the channel has
+    /// to be the token's user. Editors run the tool with a token the broadcaster logged in for.
+    pub async fn check_channel(&mut self, login: &str) -> Result<()> {
+        self.refresh().await?;
+
+        let user = self
+            .client
+            .get_user_from_login(login, &self.token)
+            .await?
+            .ok_or_else(|| Error::UnknownChannel(login.to_string()))?;
+        if user.id != self.broadcaster_id {
+            return Err(Error::WrongChannel {
+                channel: user.login.to_string(),
+                login: self.token.login.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the active or locked prediction, if any
+    pub async fn current(&mut self) -> Result<Option<Prediction>> {
+        self.refresh().await?;
+
+        get_last_prediction(&self.client, &self.token, &self.broadcaster_id).await
+    }
+
+    /// Returns the active or locked prediction, failing if there is none
+    pub async fn require_current(&mut self) -> Result<Prediction> {
+        self.current().await?.ok_or(Error::NoActivePrediction)
+    }
+
+    /// Returns the prediction with the given id, whatever its status
+    pub async fn get(&mut self, prediction_id: &PredictionIdRef) -> Result<Prediction> {
+        self.refresh().await?;
+
+        get_prediction(
+            &self.client,
+            &self.token,
+            &self.broadcaster_id,
+            prediction_id,
+        )
+        .await
+    }
+
+    /// Locks the prediction so viewers can no longer predict
+    pub async fn lock(&mut self, prediction_id: &PredictionIdRef) -> Result<Recorded> {
+        self.end(prediction_id, PredictionStatus::Locked, None)
+            .await
+    }
+
+    /// Resolves the prediction, paying out channel points to those who picked the winning outcome
+    pub async fn resolve(
+        &mut self,
+        prediction_id: &PredictionIdRef,
+        winning_outcome_id: &str,
+    ) -> Result<Recorded> {
+        self.end(
+            prediction_id,
+            PredictionStatus::Resolved,
+            Some(winning_outcome_id.to_string()),
+        )
+        .await
+    }
+
+    /// Cancels the prediction, refunding all channel points
+    pub async fn cancel(&mut self, prediction_id: &PredictionIdRef) -> Result<Recorded> {
+        self.end(prediction_id, PredictionStatus::Canceled, None)
+            .await
+    }
+
+    async fn end(
+        &mut self,
+        prediction_id: &PredictionIdRef,
+        new_status: PredictionStatus,
+        winning_outcome_id: Option<String>,
+    ) -> Result<Recorded> {
+        // The prediction may have been running for longer than the token is valid
+        self.refresh().await?;
+
+        let action = match new_status {
+            PredictionStatus::Locked => JournalAction::Locked,
+            PredictionStatus::Resolved => JournalAction::Resolved,
+            _ => JournalAction::Canceled,
+        };
+        let prediction = end_prediction(
+            &self.client,
+            &self.token,
+            &self.broadcaster_id,
+            prediction_id,
+            new_status,
+            winning_outcome_id,
+        )
+        .await?;
+
+        Ok(self.record(action, prediction))
+    }
+
+    /// Returns past predictions, newest first, going through as many pages as needed
+    ///
+    /// Stops after `limit` predictions or at the first prediction created before `since`,
+    /// and returns every prediction of the channel if neither is given.
+    pub async fn history(
+        &mut self,
+        limit: Option<usize>,
+        since: Option<OffsetDateTime>,
+    ) -> Result<Vec<Prediction>> {
+        self.refresh().await?;
+
+        list_predictions(
+            &self.client,
+            &self.token,
+            &self.broadcaster_id,
+            limit,
+            since,
+        )
+        .await
+    }
+
+    /// Subscribes to the channel's prediction events on the EventSub WebSocket at `url`
+    pub async fn subscribe(&mut self, url: &str) -> Result<PredictionEvents> {
+        self.refresh().await?;
+
+        PredictionEvents::subscribe(url, &self.client, &self.token, &self.broadcaster_id).await
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{config, Error, Result};
+
+/// A named, reusable prediction from the templates file
+///
+/// ```toml
+/// [boss]
+/// title = "Will we beat the boss?"
+/// outcomes = ["Yes", "No"]
+/// prediction_window = 120
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub outcomes: Vec<String>,
+    pub prediction_window: Option<i64>,
+}
+
+/// The default location of the templates file
+pub fn default_path() -> Result<PathBuf> {
+    Ok(config::config_dir()?.join("templates.toml"))
+}
+
+/// Loads every template from the templates file at `path`
+pub fn load(path: &Path) -> Result<BTreeMap<String, Template>> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        Error::Storage(format!(
+            "Unable to read templates from {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    let templates = toml::from_str(&contents).map_err(|e| {
+        Error::Storage(format!(
+            "Unable to parse templates in {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    Ok(templates)
+}
+
+/// Loads the template called `name` from the templates file at `path`
+pub fn find(path: &Path, name: &str) -> Result<Template> {
+    let mut templates = load(path)?;
+
+    templates.remove(name).ok_or_else(|| {
+        let available: Vec<&str> = templates.keys().map(String::as_str).collect();
+        Error::InvalidArguments(format!(
+            "No template named {name:?} in {}, available templates are: {}",
+            path.display(),
+            available.join(", ")
+        ))
+    })
+}
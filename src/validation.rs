@@ -0,0 +1,122 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// The number of outcomes a prediction must have
+pub const OUTCOMES: RangeInclusive<usize> = 2..=5;
+
+/// The longest title Twitch accepts, in characters
+pub const MAX_TITLE_LENGTH: usize = 45;
+
+/// The longest outcome title Twitch accepts, in characters
+pub const MAX_OUTCOME_LENGTH: usize = 25;
+
+/// The prediction window Twitch accepts, in seconds
+pub const PREDICTION_WINDOW: RangeInclusive<i64> = 30..=1800;
+
+/// The prediction window used when none is given, in seconds
+pub const DEFAULT_PREDICTION_WINDOW: i64 = 30;
+
+/// [`DEFAULT_PREDICTION_WINDOW`], for `#[serde(default = "...")]` which only takes functions
+pub fn default_prediction_window() -> i64 {
+    DEFAULT_PREDICTION_WINDOW
+}
+
+/// A single problem with a prediction, tied to the field it was found in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Name of the offending field, e.g. `title` or `outcomes[2]`
+    pub field: String,
+    pub message: String,
+}
+
+/// Every problem found with a prediction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationErrors(pub Vec<Violation>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The prediction is invalid:")?;
+        for violation in &self.0 {
+            write!(f, "\n  {}: {}", violation.field, violation.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Checks a prediction against the limits of the Helix Create Prediction endpoint
+///
+/// All violations are collected, so they can be fixed at once instead of one request at a time.
+pub fn validate_prediction(
+    title: &str,
+    outcomes: &[String],
+    prediction_window: i64,
+) -> Result<(), ValidationErrors> {
+    let mut violations = Vec::new();
+    let mut violation = |field: String, message: String| {
+        violations.push(Violation { field, message });
+    };
+
+    let title_length = title.chars().count();
+    if title.trim().is_empty() {
+        violation("title".to_string(), "must not be empty".to_string());
+    } else if title_length > MAX_TITLE_LENGTH {
+        violation(
+            "title".to_string(),
+            format!("must be at most {MAX_TITLE_LENGTH} characters, is {title_length}"),
+        );
+    }
+
+    if !OUTCOMES.contains(&outcomes.len()) {
+        violation(
+            "outcomes".to_string(),
+            format!(
+                "must have between {} and {} outcomes, has {}",
+                OUTCOMES.start(),
+                OUTCOMES.end(),
+                outcomes.len()
+            ),
+        );
+    }
+
+    for (i, outcome) in outcomes.iter().enumerate() {
+        let field = format!("outcomes[{}]", i + 1);
+        let outcome_length = outcome.chars().count();
+
+        if outcome.trim().is_empty() {
+            violation(field, "must not be empty".to_string());
+        } else if outcome_length > MAX_OUTCOME_LENGTH {
+            violation(
+                field,
+                format!("must be at most {MAX_OUTCOME_LENGTH} characters, is {outcome_length}"),
+            );
+        } else if let Some(first) = outcomes[..i]
+            .iter()
+            .position(|other| other.trim().to_lowercase() == outcome.trim().to_lowercase())
+        {
+            violation(
+                field,
+                format!("duplicates outcomes[{}] {:?}", first + 1, outcomes[first]),
+            );
+        }
+    }
+
+    if !PREDICTION_WINDOW.contains(&prediction_window) {
+        violation(
+            "prediction_window".to_string(),
+            format!(
+                "must be between {} and {} seconds, is {prediction_window}",
+                PREDICTION_WINDOW.start(),
+                PREDICTION_WINDOW.end()
+            ),
+        );
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors(violations))
+    }
+}
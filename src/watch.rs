@@ -0,0 +1,224 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use clap::Args;
+use console::Term;
+use prediction_creator::eventsub::PredictionEvents;
+use prediction_creator::{find_outcome, Error, PredictionService};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+use twitch_api::helix::predictions::Prediction;
+
+use crate::output::{Output, PredictionDocument};
+use crate::{events, print_end_prediction};
+
+/// How often a tailed file is checked for new lines
+const TAIL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Where the winning outcome is read from, one message per line or request
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct SourceArgs {
+    /// Tail this file, reading lines appended after the command started
+    #[clap(long)]
+    file: Option<PathBuf>,
+
+    /// Read lines from this named pipe, created beforehand with `mkfifo`
+    #[clap(long)]
+    pipe: Option<PathBuf>,
+
+    /// Listen for POST requests on this local address, e.g. 127.0.0.1:8787, the body being the message
+    #[clap(long)]
+    webhook: Option<SocketAddr>,
+}
+
+/// What a message from the source asks for
+enum Message {
+    Resolve(String),
+    Cancel,
+}
+
+impl Message {
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "" => None,
+            line if line.eq_ignore_ascii_case("cancel") => Some(Message::Cancel),
+            line => Some(Message::Resolve(line.to_string())),
+        }
+    }
+}
+
+/// Waits for a message naming the winning outcome, or `cancel`, and ends the prediction accordingly
+///
+/// Messages not matching any outcome are reported and skipped. The prediction is canceled
+/// if no usable message arrives within `timeout`, and left alone if `events` show it
+/// was ended elsewhere.
+pub async fn run(
+    term: &mut Term,
+    output: &Output,
+    service: &mut PredictionService,
+    mut prediction: Prediction,
+    mut messages: Messages,
+    mut events: Option<PredictionEvents>,
+    timeout: Duration,
+) -> anyhow::Result<Prediction> {
+    writeln!(
+        term,
+        "Waiting up to {}s for the winning outcome or \"cancel\"",
+        timeout.as_secs()
+    )?;
+
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    let (ended, verb) = loop {
+        tokio::select! {
+            _ = &mut deadline => {
+                writeln!(term, "{}", console::style("No winner received in time, cancelling").yellow())?;
+                break (output.journaled(service.cancel(&prediction.id).await?), "canceled");
+            }
+            message = messages.recv() => {
+                let message = message.ok_or_else(|| anyhow::anyhow!("The source stopped unexpectedly"))??;
+                match Message::parse(&message) {
+                    None => {}
+                    Some(Message::Cancel) => break (output.journaled(service.cancel(&prediction.id).await?), "canceled"),
+                    Some(Message::Resolve(winner)) => match find_outcome(&prediction.outcomes, &winner) {
+                        Ok(outcome) => {
+                            let outcome_id = outcome.id.clone();
+                            break (output.journaled(service.resolve(&prediction.id, &outcome_id).await?), "resolved");
+                        }
+                        Err(e @ (Error::UnknownOutcome { .. } | Error::AmbiguousOutcome { .. })) => {
+                            writeln!(term, "{} {e}", console::style("Ignoring message:").yellow())?;
+                        }
+                        Err(e) => return Err(e.into()),
+                    },
+                }
+            }
+            event = events::next(&mut events) => match event {
+                Ok(event) => {
+                    event.apply(&mut prediction);
+                    if event.ends(&prediction) {
+                        writeln!(term, "{}", console::style("The prediction was ended elsewhere").yellow())?;
+                        break (prediction, "ended");
+                    }
+                }
+                Err(e) => {
+                    events::warn(term, &e)?;
+                    events = None;
+                }
+            },
+        }
+    };
+
+    print_end_prediction(term, &ended, verb)?;
+    let action = match verb {
+        "ended" => "ended_elsewhere",
+        verb => verb,
+    };
+    output.emit(&PredictionDocument::new(action, Some(&ended)));
+
+    Ok(ended)
+}
+
+/// Messages read from the source, or the error that made it stop
+pub type Messages = mpsc::Receiver<anyhow::Result<String>>;
+
+/// Starts reading messages from the source in the background
+///
+/// Only messages sent from now on are read, so this is done before creating the prediction.
+pub async fn listen(source: SourceArgs) -> anyhow::Result<Messages> {
+    let (tx, rx) = mpsc::channel(16);
+
+    if let Some(path) = source.file {
+        // Only lines written from now on count, anything already there is from an earlier prediction
+        let start = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+        tokio::spawn(async move {
+            if let Err(e) = tail(&path, start, &tx).await {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+    } else if let Some(path) = source.pipe {
+        std::thread::spawn(move || {
+            if let Err(e) = read_pipe(&path, &tx) {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+    } else if let Some(addr) = source.webhook {
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to listen on {addr}: {e}"))?;
+        let app = Router::new().route("/", post(webhook)).with_state(tx);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+    }
+
+    Ok(rx)
+}
+
+/// Sends every line appended to the file after `position`, starting over if it is truncated
+async fn tail(
+    path: &Path,
+    mut position: u64,
+    tx: &mpsc::Sender<anyhow::Result<String>>,
+) -> anyhow::Result<()> {
+    let mut pending = String::new();
+
+    loop {
+        tokio::time::sleep(TAIL_INTERVAL).await;
+
+        let mut file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => anyhow::bail!("Unable to read {}: {e}", path.display()),
+        };
+        if file.metadata().await?.len() < position {
+            position = 0;
+            pending.clear();
+        }
+
+        file.seek(std::io::SeekFrom::Start(position)).await?;
+        let mut appended = Vec::new();
+        position += file.read_to_end(&mut appended).await? as u64;
+        pending.push_str(&String::from_utf8_lossy(&appended));
+
+        // Keep a trailing partial line until the rest of it is written
+        while let Some(end) = pending.find('\n') {
+            let line: String = pending.drain(..=end).collect();
+            if tx.send(Ok(line)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Sends every line written to the named pipe, reopening it whenever a writer closes it
+fn read_pipe(path: &Path, tx: &mpsc::Sender<anyhow::Result<String>>) -> anyhow::Result<()> {
+    loop {
+        // Blocks until a writer opens the pipe
+        let pipe = std::fs::File::open(path)
+            .map_err(|e| anyhow::anyhow!("Unable to open {}: {e}", path.display()))?;
+        for line in BufReader::new(pipe).lines() {
+            if tx.blocking_send(Ok(line?)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn webhook(
+    State(tx): State<mpsc::Sender<anyhow::Result<String>>>,
+    body: String,
+) -> StatusCode {
+    match tx.send(Ok(body)).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::GONE,
+    }
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::fs;
+
+use common::{spawn, MockTwitch, TOKEN};
+
+#[tokio::test]
+async fn moderators_run_predictions_from_chat() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let _bot = spawn(&mock, home.path(), Some(TOKEN), &["bot"]);
+    mock.wait_for_chat_join().await;
+
+    // Viewers can't start predictions
+    mock.say("viewer", "subscriber/12", "!predict \"Not yours\" a b");
+    mock.say(
+        "a_mod",
+        "moderator/1",
+        "!predict \"Will we beat the boss?\" Yes \"No way\" 120",
+    );
+    let replies = mock.wait_for_chat_replies(1).await;
+    assert!(
+        replies[0].contains("\"Will we beat the boss?\" started"),
+        "{replies:?}"
+    );
+    let prediction = &mock.predictions()[0];
+    assert_eq!(mock.predictions().len(), 1);
+    assert_eq!(prediction["prediction_window"], 120);
+    assert_eq!(prediction["outcomes"][1]["title"], "No way");
+
+    mock.say("mock_broadcaster", "broadcaster/1", "!lock");
+    mock.wait_for_chat_replies(2).await;
+    assert_eq!(mock.predictions()[0]["status"], "LOCKED");
+
+    mock.say("viewer", "", "!cancel");
+    mock.say("a_mod", "moderator/1,partner/1", "!winner 2");
+    let replies = mock.wait_for_chat_replies(3).await;
+    assert!(replies[2].contains("No way won"), "{replies:?}");
+    let prediction = &mock.predictions()[0];
+    assert_eq!(prediction["status"], "RESOLVED");
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+    assert_eq!(mock.chat_replies().len(), 3);
+}
+
+#[tokio::test]
+async fn reports_errors_in_chat() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let _bot = spawn(&mock, home.path(), Some(TOKEN), &["bot"]);
+    mock.wait_for_chat_join().await;
+
+    mock.say("a_mod", "moderator/1", "!winner 1");
+    mock.say("a_mod", "moderator/1", "!predict \"Missing outcomes\"");
+    let replies = mock.wait_for_chat_replies(2).await;
+    assert_eq!(replies[0], "There is no active prediction");
+    assert!(replies[1].starts_with("Usage: !predict"), "{replies:?}");
+}
+
+#[tokio::test]
+async fn reconnects_after_the_connection_fails() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let mut bot = spawn(&mock, home.path(), Some(TOKEN), &["bot"]);
+    mock.wait_for_chat_join().await;
+
+    mock.drop_chat();
+    mock.wait_for_chat_join().await;
+    assert!(bot.try_wait().unwrap().is_none(), "the bot exited");
+
+    mock.say("a_mod", "moderator/1", "!cancel");
+    let replies = mock.wait_for_chat_replies(1).await;
+    assert_eq!(replies[0], "There is no active prediction");
+}
+
+#[tokio::test]
+async fn refreshes_the_token_before_connecting() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let (access_token, refresh_token) = mock.issue_token(10);
+    let path = home.path().join(".config/prediction-creator/token.json");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(
+        &path,
+        serde_json::json!({
+            "client_id": common::CLIENT_ID,
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "login": common::BROADCASTER_LOGIN,
+            "user_id": common::BROADCASTER_ID,
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let mut bot = spawn(&mock, home.path(), None, &["bot"]);
+    mock.wait_for_chat_join().await;
+
+    // Chat would reject the old token when reconnecting
+    mock.expire_token(&access_token);
+    mock.drop_chat();
+    mock.wait_for_chat_join().await;
+    assert!(bot.try_wait().unwrap().is_none(), "the bot exited");
+    assert!(mock.requests().contains(&"POST /oauth2/token".to_string()));
+
+    mock.say("a_mod", "moderator/1", "!cancel");
+    let replies = mock.wait_for_chat_replies(1).await;
+    assert_eq!(replies[0], "There is no active prediction");
+}
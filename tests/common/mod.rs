@@ -0,0 +1,1125 @@
+//! An in-process mock of the Twitch Helix predictions, EventSub, chat and OAuth endpoints, used to
+//! run the `prediction-creator` binary end to end without a live channel.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::process::{Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Form, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+pub const CLIENT_ID: &str = "mock-client-id";
+pub const BROADCASTER_ID: &str = "12345";
+pub const BROADCASTER_LOGIN: &str = "mock_broadcaster";
+/// A valid token for the mock broadcaster, carrying every scope we need
+pub const TOKEN: &str = "mock-token";
+/// A valid token for the mock broadcaster, missing `channel:manage:predictions`
+pub const TOKEN_WITHOUT_SCOPES: &str = "mock-token-without-scopes";
+pub const CO_STREAMER_ID: &str = "67890";
+pub const CO_STREAMER_LOGIN: &str = "mock_costreamer";
+/// A valid token for another channel, carrying every scope we need
+pub const CO_STREAMER_TOKEN: &str = "mock-costreamer-token";
+
+const PREDICTIONS_SCOPE: &str = "channel:manage:predictions";
+/// Sent to a chat session to make it drop the connection without a closing handshake
+const DROP_CONNECTION: &str = "\0drop";
+const CHAT_SCOPES: [&str; 2] = ["chat:read", "chat:edit"];
+
+#[derive(Debug, Clone)]
+struct TokenInfo {
+    user_id: String,
+    login: String,
+    scopes: Vec<String>,
+    expires_in: u64,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    tokens: HashMap<String, TokenInfo>,
+    refresh_tokens: HashMap<String, String>,
+    /// Predictions, newest first like Helix returns them
+    predictions: Vec<Value>,
+    next_id: u64,
+    requests: Vec<String>,
+    /// Status and message every Helix request fails with, if set
+    failure: Option<(StatusCode, String)>,
+    /// Open EventSub WebSocket sessions, by session id
+    sessions: HashMap<String, mpsc::UnboundedSender<String>>,
+    /// EventSub subscriptions as their type and session id
+    subscriptions: Vec<(String, String)>,
+    /// Chat connections that joined the broadcaster's channel
+    chat_sessions: Vec<mpsc::UnboundedSender<String>>,
+    /// Every line sent to chat by a client
+    chat_sent: Vec<String>,
+}
+
+impl MockState {
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}-{}", self.next_id)
+    }
+
+    fn issue_token(&mut self, expires_in: u64) -> (String, String) {
+        let access_token = self.next_id("access");
+        let refresh_token = self.next_id("refresh");
+        self.tokens.insert(
+            access_token.clone(),
+            TokenInfo {
+                user_id: BROADCASTER_ID.to_string(),
+                login: BROADCASTER_LOGIN.to_string(),
+                scopes: all_scopes(),
+                expires_in,
+            },
+        );
+        self.refresh_tokens
+            .insert(refresh_token.clone(), access_token.clone());
+        (access_token, refresh_token)
+    }
+
+    /// Sends `event` to every session subscribed to `subscription_type`, in the format of
+    /// the Twitch CLI websocket server
+    fn notify(&mut self, subscription_type: &str, event: Value) {
+        for (kind, session_id) in &self.subscriptions {
+            if kind != subscription_type {
+                continue;
+            }
+            let Some(session) = self.sessions.get(session_id) else {
+                continue;
+            };
+            let message = json!({
+                "metadata": {
+                    "message_id": format!("message-{}", self.next_id + 1),
+                    "message_type": "notification",
+                    "message_timestamp": now(),
+                    "subscription_type": subscription_type,
+                    "subscription_version": "1",
+                },
+                "payload": {
+                    "subscription": {
+                        "id": format!("subscription-{kind}"),
+                        "status": "enabled",
+                        "type": subscription_type,
+                        "version": "1",
+                        "condition": { "broadcaster_user_id": BROADCASTER_ID },
+                        "transport": { "method": "websocket", "session_id": session_id },
+                        "created_at": now(),
+                        "cost": 0,
+                    },
+                    "event": event,
+                },
+            });
+            let _ = session.send(message.to_string());
+        }
+        self.next_id += 1;
+    }
+}
+
+/// A prediction as EventSub describes it, which differs from Helix in field names and casing
+fn prediction_event(prediction: &Value) -> Value {
+    let outcomes: Vec<Value> = prediction["outcomes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|outcome| {
+            json!({
+                "id": outcome["id"],
+                "title": outcome["title"],
+                "color": outcome["color"].as_str().unwrap().to_lowercase(),
+                "users": outcome["users"],
+                "channel_points": outcome["channel_points"],
+                "top_predictors": outcome["top_predictors"],
+            })
+        })
+        .collect();
+
+    let mut event = json!({
+        "id": prediction["id"],
+        "broadcaster_user_id": prediction["broadcaster_id"],
+        "broadcaster_user_login": prediction["broadcaster_login"],
+        "broadcaster_user_name": prediction["broadcaster_name"],
+        "title": prediction["title"],
+        "outcomes": outcomes,
+        "started_at": prediction["created_at"],
+        "locks_at": prediction["created_at"],
+    });
+    match prediction["status"].as_str() {
+        Some("LOCKED") => event["locked_at"] = prediction["locked_at"].clone(),
+        Some(status @ ("RESOLVED" | "CANCELED")) => {
+            event["status"] = json!(status.to_lowercase());
+            event["winning_outcome_id"] = prediction["winning_outcome_id"].clone();
+            event["ended_at"] = prediction["ended_at"].clone();
+        }
+        _ => {}
+    }
+
+    event
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+fn all_scopes() -> Vec<String> {
+    [PREDICTIONS_SCOPE]
+        .into_iter()
+        .chain(CHAT_SCOPES)
+        .map(str::to_string)
+        .collect()
+}
+
+/// A running mock of the Twitch endpoints
+pub struct MockTwitch {
+    pub addr: SocketAddr,
+    state: SharedState,
+}
+
+impl MockTwitch {
+    /// Starts the mock on a random local port
+    pub async fn start() -> Self {
+        let mut state = MockState::default();
+        state.tokens.insert(
+            TOKEN.to_string(),
+            TokenInfo {
+                user_id: BROADCASTER_ID.to_string(),
+                login: BROADCASTER_LOGIN.to_string(),
+                scopes: all_scopes(),
+                expires_in: 3600,
+            },
+        );
+        state.tokens.insert(
+            CO_STREAMER_TOKEN.to_string(),
+            TokenInfo {
+                user_id: CO_STREAMER_ID.to_string(),
+                login: CO_STREAMER_LOGIN.to_string(),
+                scopes: all_scopes(),
+                expires_in: 3600,
+            },
+        );
+        state.tokens.insert(
+            TOKEN_WITHOUT_SCOPES.to_string(),
+            TokenInfo {
+                user_id: BROADCASTER_ID.to_string(),
+                login: BROADCASTER_LOGIN.to_string(),
+                scopes: vec!["chat:read".to_string()],
+                expires_in: 3600,
+            },
+        );
+        let state = Arc::new(Mutex::new(state));
+
+        let app = Router::new()
+            .route("/oauth2/validate", get(validate))
+            .route("/oauth2/device", post(device))
+            .route("/oauth2/token", post(token))
+            .route(
+                "/helix/predictions",
+                get(get_predictions)
+                    .post(create_prediction)
+                    .patch(end_prediction),
+            )
+            .route("/helix/users", get(get_users))
+            .route("/helix/eventsub/subscriptions", post(create_subscription))
+            .route("/eventsub/ws", get(eventsub_ws))
+            .route("/irc", get(irc_ws))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { addr, state }
+    }
+
+    pub fn helix_url(&self) -> String {
+        format!("http://{}/helix/", self.addr)
+    }
+
+    pub fn auth_url(&self) -> String {
+        format!("http://{}/oauth2/", self.addr)
+    }
+
+    pub fn eventsub_url(&self) -> String {
+        format!("ws://{}/eventsub/ws", self.addr)
+    }
+
+    pub fn irc_url(&self) -> String {
+        format!("ws://{}/irc", self.addr)
+    }
+
+    /// Issues a token for the mock broadcaster that expires in `expires_in` seconds,
+    /// returning the access and refresh token
+    pub fn issue_token(&self, expires_in: u64) -> (String, String) {
+        self.state.lock().unwrap().issue_token(expires_in)
+    }
+
+    /// Expires `access_token` like Twitch does after a few hours, so it fails validation
+    /// while its refresh token keeps working
+    pub fn expire_token(&self, access_token: &str) {
+        self.state.lock().unwrap().tokens.remove(access_token);
+    }
+
+    /// Every prediction created so far, newest first
+    pub fn predictions(&self) -> Vec<Value> {
+        self.state.lock().unwrap().predictions.clone()
+    }
+
+    /// The method and path of every request received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Adds a prediction that was resolved in favour of its first outcome, as the newest prediction
+    pub fn add_resolved_prediction(&self, title: &str, created_at: &str) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id("prediction");
+        let outcomes: Vec<Value> = ["Yes", "No"]
+            .iter()
+            .enumerate()
+            .map(|(i, outcome)| {
+                json!({
+                    "id": format!("{id}-outcome-{}", i + 1),
+                    "title": outcome,
+                    "users": 1,
+                    "channel_points": 100,
+                    "top_predictors": [],
+                    "color": if i == 0 { "BLUE" } else { "PINK" },
+                })
+            })
+            .collect();
+        let prediction = json!({
+            "id": id,
+            "broadcaster_id": BROADCASTER_ID,
+            "broadcaster_name": BROADCASTER_LOGIN,
+            "broadcaster_login": BROADCASTER_LOGIN,
+            "title": title,
+            "winning_outcome_id": format!("{id}-outcome-1"),
+            "outcomes": outcomes,
+            "prediction_window": 60,
+            "status": "RESOLVED",
+            "created_at": created_at,
+            "ended_at": created_at,
+            "locked_at": created_at,
+        });
+        state.predictions.insert(0, prediction);
+    }
+
+    /// Waits until the newest prediction has `status`, e.g. for a spawned command to get going
+    pub async fn wait_for_status(&self, status: &str) {
+        for _ in 0..100 {
+            if self.predictions().first().map(|p| p["status"] == status) == Some(true) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("no prediction reached {status}");
+    }
+
+    /// The types of the EventSub subscriptions created so far
+    pub fn subscriptions(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .subscriptions
+            .iter()
+            .map(|(kind, _)| kind.clone())
+            .collect()
+    }
+
+    /// Cancels the newest prediction as if a moderator did so on Twitch, notifying EventSub subscribers
+    pub fn cancel_elsewhere(&self) {
+        let mut state = self.state.lock().unwrap();
+        let prediction = state
+            .predictions
+            .first_mut()
+            .expect("a prediction to exist");
+        prediction["status"] = json!("CANCELED");
+        prediction["ended_at"] = json!(now());
+        let event = prediction_event(prediction);
+        state.notify("channel.prediction.end", event);
+    }
+
+    /// Posts `text` to the broadcaster's chat as `user`, with `badges` like `moderator/1`
+    pub fn say(&self, user: &str, badges: &str, text: &str) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id("message");
+        let line = format!(
+            "@badges={badges};display-name={user};id={id};mod=0 :{user}!{user}@{user}.tmi.twitch.tv PRIVMSG #{BROADCASTER_LOGIN} :{text}"
+        );
+        for session in &state.chat_sessions {
+            let _ = session.send(line.clone());
+        }
+    }
+
+    /// Drops every chat connection without a closing handshake, as a network failure would
+    pub fn drop_chat(&self) {
+        let mut state = self.state.lock().unwrap();
+        for session in state.chat_sessions.drain(..) {
+            let _ = session.send(DROP_CONNECTION.to_string());
+        }
+    }
+
+    /// The text of every message sent to chat by a client
+    pub fn chat_replies(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .chat_sent
+            .iter()
+            .filter_map(|line| line.split_once(" :").map(|(_, text)| text.to_string()))
+            .collect()
+    }
+
+    /// Waits until a client joined the broadcaster's chat
+    pub async fn wait_for_chat_join(&self) {
+        for _ in 0..100 {
+            if !self.state.lock().unwrap().chat_sessions.is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("nobody joined the chat");
+    }
+
+    /// Waits until `count` messages were sent to chat, returning their text
+    pub async fn wait_for_chat_replies(&self, count: usize) -> Vec<String> {
+        for _ in 0..100 {
+            let replies = self.chat_replies();
+            if replies.len() >= count {
+                return replies;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!(
+            "fewer than {count} chat messages: {:?}",
+            self.chat_replies()
+        );
+    }
+
+    /// Makes every following Helix request fail with `status` and `message`
+    pub fn fail_with(&self, status: StatusCode, message: &str) {
+        self.state.lock().unwrap().failure = Some((status, message.to_string()));
+    }
+
+    /// Casts `points` channel points from `user` on the outcome at `index` of the newest prediction
+    pub fn predict(&self, index: usize, user: &str, points: i64) {
+        let mut state = self.state.lock().unwrap();
+        let prediction = state
+            .predictions
+            .first_mut()
+            .expect("a prediction to exist");
+        let outcome = &mut prediction["outcomes"][index];
+        outcome["users"] = json!(outcome["users"].as_i64().unwrap() + 1);
+        outcome["channel_points"] = json!(outcome["channel_points"].as_i64().unwrap() + points);
+        let predictors = outcome["top_predictors"].as_array_mut().unwrap();
+        predictors.push(json!({
+            "user_id": format!("user-{user}"),
+            "user_login": user,
+            "user_name": user,
+            "channel_points_used": points,
+            "channel_points_won": null,
+        }));
+        let event = prediction_event(prediction);
+        state.notify("channel.prediction.progress", event);
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": status.canonical_reason(),
+            "status": status.as_u16(),
+            "message": message,
+        })),
+    )
+        .into_response()
+}
+
+fn now() -> String {
+    twitch_types::Timestamp::now().take()
+}
+
+/// Checks the bearer token and client id of a Helix request
+fn authorize(state: &MockState, headers: &HeaderMap) -> Result<TokenInfo, Box<Response>> {
+    if let Some((status, ref message)) = state.failure {
+        return Err(Box::new(error(status, message)));
+    }
+
+    let token = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| Box::new(error(StatusCode::UNAUTHORIZED, "OAuth token is missing")))?;
+
+    if headers.get("client-id").is_none() {
+        return Err(Box::new(error(
+            StatusCode::UNAUTHORIZED,
+            "Client ID is missing",
+        )));
+    }
+
+    let info = state
+        .tokens
+        .get(token)
+        .cloned()
+        .ok_or_else(|| Box::new(error(StatusCode::UNAUTHORIZED, "Invalid OAuth token")))?;
+
+    if !info.scopes.iter().any(|scope| scope == PREDICTIONS_SCOPE) {
+        return Err(Box::new(error(
+            StatusCode::UNAUTHORIZED,
+            "Missing scope: channel:manage:predictions",
+        )));
+    }
+
+    Ok(info)
+}
+
+async fn validate(State(state): State<SharedState>, headers: HeaderMap) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("GET /oauth2/validate".to_string());
+
+    let token = headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("OAuth "));
+
+    match token.and_then(|token| state.tokens.get(token)) {
+        Some(info) => Json(json!({
+            "client_id": CLIENT_ID,
+            "login": info.login,
+            "scopes": info.scopes,
+            "user_id": info.user_id,
+            "expires_in": info.expires_in,
+        }))
+        .into_response(),
+        None => error(StatusCode::UNAUTHORIZED, "invalid access token"),
+    }
+}
+
+async fn device(
+    State(state): State<SharedState>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("POST /oauth2/device".to_string());
+
+    if form.get("client_id").map(String::as_str) != Some(CLIENT_ID) {
+        return error(StatusCode::BAD_REQUEST, "invalid client");
+    }
+
+    Json(json!({
+        "device_code": "mock-device-code",
+        "expires_in": 1800,
+        "interval": 1,
+        "user_code": "MOCKCODE",
+        "verification_uri": "https://www.twitch.tv/activate?device-code=MOCKCODE",
+    }))
+    .into_response()
+}
+
+async fn token(
+    State(state): State<SharedState>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("POST /oauth2/token".to_string());
+
+    if form.get("client_id").map(String::as_str) != Some(CLIENT_ID) {
+        return error(StatusCode::BAD_REQUEST, "invalid client");
+    }
+
+    let (access_token, refresh_token) = match form.get("grant_type").map(String::as_str) {
+        Some("urn:ietf:params:oauth:grant-type:device_code") => {
+            if form.get("device_code").map(String::as_str) != Some("mock-device-code") {
+                return error(StatusCode::BAD_REQUEST, "invalid device code");
+            }
+            state.issue_token(3600)
+        }
+        Some("refresh_token") => {
+            let old = form
+                .get("refresh_token")
+                .and_then(|refresh_token| state.refresh_tokens.remove(refresh_token));
+            let Some(old) = old else {
+                return error(StatusCode::BAD_REQUEST, "Invalid refresh token");
+            };
+            state.tokens.remove(&old);
+            state.issue_token(3600)
+        }
+        _ => return error(StatusCode::BAD_REQUEST, "unsupported grant type"),
+    };
+
+    Json(json!({
+        "access_token": access_token,
+        "expires_in": 3600,
+        "refresh_token": refresh_token,
+        "scope": all_scopes(),
+        "token_type": "bearer",
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct GetPredictionsQuery {
+    broadcaster_id: String,
+    id: Option<String>,
+    first: Option<usize>,
+    after: Option<String>,
+}
+
+async fn get_predictions(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<GetPredictionsQuery>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("GET /helix/predictions".to_string());
+
+    let info = match authorize(&state, &headers) {
+        Ok(info) => info,
+        Err(response) => return *response,
+    };
+    if query.broadcaster_id != info.user_id {
+        return error(
+            StatusCode::UNAUTHORIZED,
+            "The ID in broadcaster_id must match the user ID found in the request's OAuth token.",
+        );
+    }
+
+    let predictions: Vec<&Value> = state
+        .predictions
+        .iter()
+        .filter(|prediction| prediction["broadcaster_id"] == query.broadcaster_id.as_str())
+        .collect();
+
+    if let Some(id) = query.id {
+        let data: Vec<&Value> = predictions
+            .into_iter()
+            .filter(|prediction| prediction["id"] == id.as_str())
+            .collect();
+        return Json(json!({ "data": data, "pagination": {} })).into_response();
+    }
+
+    let first = query.first.unwrap_or(20);
+    if !(1..=25).contains(&first) {
+        return error(StatusCode::BAD_REQUEST, "first must be between 1 and 25");
+    }
+    let start: usize = query
+        .after
+        .and_then(|cursor| cursor.parse().ok())
+        .unwrap_or(0);
+    let end = (start + first).min(predictions.len());
+    let data = &predictions[start.min(end)..end];
+    let pagination = if end < predictions.len() {
+        json!({ "cursor": end.to_string() })
+    } else {
+        json!({})
+    };
+
+    Json(json!({ "data": data, "pagination": pagination })).into_response()
+}
+
+#[derive(Deserialize)]
+struct GetUsersQuery {
+    login: String,
+}
+
+async fn get_users(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<GetUsersQuery>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("GET /helix/users".to_string());
+
+    if let Err(response) = authorize(&state, &headers) {
+        return *response;
+    }
+
+    let data: Vec<Value> = [
+        (BROADCASTER_ID, BROADCASTER_LOGIN),
+        (CO_STREAMER_ID, CO_STREAMER_LOGIN),
+    ]
+    .into_iter()
+    .filter(|(_, login)| login.eq_ignore_ascii_case(&query.login))
+    .map(|(id, login)| {
+        json!({
+            "id": id,
+            "login": login,
+            "display_name": login,
+            "type": "",
+            "broadcaster_type": "affiliate",
+            "description": "",
+            "profile_image_url": "",
+            "offline_image_url": "",
+            "view_count": 0,
+            "created_at": "2020-01-01T00:00:00Z",
+        })
+    })
+    .collect();
+
+    Json(json!({ "data": data })).into_response()
+}
+
+#[derive(Deserialize)]
+struct CreatePredictionBody {
+    broadcaster_id: String,
+    title: String,
+    outcomes: Vec<NewOutcome>,
+    prediction_window: i64,
+}
+
+#[derive(Deserialize)]
+struct NewOutcome {
+    title: String,
+}
+
+async fn create_prediction(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(body): Json<CreatePredictionBody>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("POST /helix/predictions".to_string());
+
+    let info = match authorize(&state, &headers) {
+        Ok(info) => info,
+        Err(response) => return *response,
+    };
+    if body.broadcaster_id != info.user_id {
+        return error(
+            StatusCode::UNAUTHORIZED,
+            "The ID in broadcaster_id must match the user ID found in the request's OAuth token.",
+        );
+    }
+    if body.title.chars().count() > 45
+        || !(2..=10).contains(&body.outcomes.len())
+        || !(30..=1800).contains(&body.prediction_window)
+    {
+        return error(StatusCode::BAD_REQUEST, "Invalid prediction");
+    }
+    if state.predictions.iter().any(|prediction| {
+        prediction["broadcaster_id"] == body.broadcaster_id.as_str()
+            && matches!(prediction["status"].as_str(), Some("ACTIVE" | "LOCKED"))
+    }) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "The broadcaster already has a prediction that's running",
+        );
+    }
+
+    let id = state.next_id("prediction");
+    let outcomes: Vec<Value> = body
+        .outcomes
+        .iter()
+        .enumerate()
+        .map(|(i, outcome)| {
+            json!({
+                "id": format!("{id}-outcome-{}", i + 1),
+                "title": outcome.title,
+                "users": 0,
+                "channel_points": 0,
+                "top_predictors": [],
+                "color": if i == 0 { "BLUE" } else { "PINK" },
+            })
+        })
+        .collect();
+    let prediction = json!({
+        "id": id,
+        "broadcaster_id": info.user_id,
+        "broadcaster_name": info.login,
+        "broadcaster_login": info.login,
+        "title": body.title,
+        "winning_outcome_id": null,
+        "outcomes": outcomes,
+        "prediction_window": body.prediction_window,
+        "status": "ACTIVE",
+        "created_at": now(),
+        "ended_at": null,
+        "locked_at": null,
+    });
+    state.predictions.insert(0, prediction.clone());
+    state.notify("channel.prediction.begin", prediction_event(&prediction));
+
+    Json(json!({ "data": [prediction] })).into_response()
+}
+
+#[derive(Deserialize)]
+struct EndPredictionBody {
+    broadcaster_id: String,
+    id: String,
+    status: String,
+    winning_outcome_id: Option<String>,
+}
+
+async fn end_prediction(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(body): Json<EndPredictionBody>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push("PATCH /helix/predictions".to_string());
+
+    let info = match authorize(&state, &headers) {
+        Ok(info) => info,
+        Err(response) => return *response,
+    };
+    if body.broadcaster_id != info.user_id {
+        return error(
+            StatusCode::UNAUTHORIZED,
+            "The ID in broadcaster_id must match the user ID found in the request's OAuth token.",
+        );
+    }
+
+    let Some(prediction) = state.predictions.iter_mut().find(|prediction| {
+        prediction["id"] == body.id.as_str()
+            && prediction["broadcaster_id"] == body.broadcaster_id.as_str()
+    }) else {
+        return error(StatusCode::NOT_FOUND, "The prediction was not found");
+    };
+
+    let current = prediction["status"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    match (current.as_str(), body.status.as_str()) {
+        ("ACTIVE", "LOCKED") => {
+            prediction["locked_at"] = json!(now());
+        }
+        ("ACTIVE" | "LOCKED", "RESOLVED") => {
+            let Some(winner) = body.winning_outcome_id else {
+                return error(StatusCode::BAD_REQUEST, "winning_outcome_id is required");
+            };
+            let outcomes = prediction["outcomes"].as_array_mut().unwrap();
+            let Some(winning) = outcomes
+                .iter()
+                .position(|outcome| outcome["id"] == winner.as_str())
+            else {
+                return error(StatusCode::BAD_REQUEST, "winning_outcome_id is invalid");
+            };
+            let total: i64 = outcomes
+                .iter()
+                .map(|outcome| outcome["channel_points"].as_i64().unwrap())
+                .sum();
+            let winning_points = outcomes[winning]["channel_points"].as_i64().unwrap();
+            for (i, outcome) in outcomes.iter_mut().enumerate() {
+                for predictor in outcome["top_predictors"].as_array_mut().unwrap() {
+                    let used = predictor["channel_points_used"].as_i64().unwrap();
+                    let won = if i == winning && winning_points > 0 {
+                        used * total / winning_points
+                    } else {
+                        0
+                    };
+                    predictor["channel_points_won"] = json!(won);
+                }
+            }
+            prediction["winning_outcome_id"] = json!(winner);
+            prediction["ended_at"] = json!(now());
+        }
+        ("ACTIVE" | "LOCKED", "CANCELED") => {
+            prediction["ended_at"] = json!(now());
+        }
+        _ => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                &format!("Can't change a {current} prediction to {}", body.status),
+            )
+        }
+    }
+    prediction["status"] = json!(body.status);
+    let prediction = prediction.clone();
+
+    let subscription_type = match body.status.as_str() {
+        "LOCKED" => "channel.prediction.lock",
+        _ => "channel.prediction.end",
+    };
+    state.notify(subscription_type, prediction_event(&prediction));
+
+    Json(json!({ "data": [prediction] })).into_response()
+}
+
+#[derive(Deserialize)]
+struct CreateSubscriptionBody {
+    #[serde(rename = "type")]
+    kind: String,
+    version: String,
+    condition: Value,
+    transport: SubscriptionTransport,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionTransport {
+    method: String,
+    session_id: String,
+}
+
+async fn create_subscription(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(body): Json<CreateSubscriptionBody>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state
+        .requests
+        .push("POST /helix/eventsub/subscriptions".to_string());
+
+    if let Err(response) = authorize(&state, &headers) {
+        return *response;
+    }
+    if body.transport.method != "websocket"
+        || !state.sessions.contains_key(&body.transport.session_id)
+    {
+        return error(StatusCode::BAD_REQUEST, "invalid transport");
+    }
+
+    let id = state.next_id("subscription");
+    state
+        .subscriptions
+        .push((body.kind.clone(), body.transport.session_id.clone()));
+
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "data": [{
+                "id": id,
+                "status": "enabled",
+                "type": body.kind,
+                "version": body.version,
+                "condition": body.condition,
+                "created_at": now(),
+                "transport": {
+                    "method": "websocket",
+                    "session_id": body.transport.session_id,
+                    "connected_at": now(),
+                },
+                "cost": 0,
+            }],
+            "total": 1,
+            "total_cost": 0,
+            "max_total_cost": 10,
+        })),
+    )
+        .into_response()
+}
+
+async fn eventsub_ws(State(state): State<SharedState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(|socket| eventsub_session(state, socket))
+}
+
+/// Welcomes the client, then forwards notifications until either side goes away
+async fn eventsub_session(state: SharedState, mut socket: WebSocket) {
+    let (tx, mut notifications) = mpsc::unbounded_channel();
+    let session_id = {
+        let mut state = state.lock().unwrap();
+        let session_id = state.next_id("session");
+        state.sessions.insert(session_id.clone(), tx);
+        session_id
+    };
+
+    let welcome = json!({
+        "metadata": {
+            "message_id": format!("{session_id}-welcome"),
+            "message_type": "session_welcome",
+            "message_timestamp": now(),
+        },
+        "payload": {
+            "session": {
+                "id": session_id,
+                "status": "connected",
+                "connected_at": now(),
+                "keepalive_timeout_seconds": 10,
+                "reconnect_url": null,
+            },
+        },
+    });
+    if socket
+        .send(Message::Text(welcome.to_string().into()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            notification = notifications.recv() => {
+                let Some(notification) = notification else { break };
+                if socket.send(Message::Text(notification.into())).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => {
+                if !matches!(message, Some(Ok(_))) {
+                    break;
+                }
+            }
+        }
+    }
+
+    state.lock().unwrap().sessions.remove(&session_id);
+}
+
+async fn irc_ws(State(state): State<SharedState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(|socket| irc_session(state, socket))
+}
+
+/// Speaks just enough of Twitch IRC to log in, join the broadcaster's channel and chat
+async fn irc_session(state: SharedState, mut socket: WebSocket) {
+    let (tx, mut chat) = mpsc::unbounded_channel::<String>();
+    let mut token = None;
+
+    loop {
+        let text = tokio::select! {
+            line = chat.recv() => {
+                let Some(line) = line else { break };
+                if line == DROP_CONNECTION {
+                    break;
+                }
+                if socket.send(Message::Text(line.into())).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => text,
+                Some(Ok(_)) => continue,
+                _ => break,
+            },
+        };
+
+        for line in text.lines() {
+            let reply = if let Some(pass) = line.strip_prefix("PASS oauth:") {
+                token = Some(pass.to_string());
+                None
+            } else if let Some(nick) = line.strip_prefix("NICK ") {
+                let state = state.lock().unwrap();
+                let valid = token
+                    .as_ref()
+                    .and_then(|token| state.tokens.get(token))
+                    .is_some_and(|info| info.login == nick);
+                if valid {
+                    Some(format!(":tmi.twitch.tv 001 {nick} :Welcome, GLHF!"))
+                } else {
+                    Some(":tmi.twitch.tv NOTICE * :Login authentication failed".to_string())
+                }
+            } else if line.starts_with("CAP REQ") {
+                Some(":tmi.twitch.tv CAP * ACK :twitch.tv/tags twitch.tv/commands".to_string())
+            } else if line == format!("JOIN #{BROADCASTER_LOGIN}") {
+                state.lock().unwrap().chat_sessions.push(tx.clone());
+                None
+            } else if line.starts_with("PING") {
+                Some(":tmi.twitch.tv PONG tmi.twitch.tv".to_string())
+            } else if line.contains(" PRIVMSG ") || line.starts_with("PRIVMSG ") {
+                state.lock().unwrap().chat_sent.push(line.to_string());
+                None
+            } else {
+                None
+            };
+
+            if let Some(reply) = reply {
+                if socket.send(Message::Text(reply.into())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// The result of running the binary
+pub struct Run {
+    pub success: bool,
+    pub code: Option<i32>,
+    /// The JSON documents on stdout, empty if stdout holds anything else, e.g. an export
+    pub documents: Vec<Value>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Run {
+    /// The last JSON document written to stdout
+    pub fn last(&self) -> &Value {
+        self.documents.last().expect("at least one JSON document")
+    }
+}
+
+/// Runs `prediction-creator --output json` against the mock, with `home` as the home directory
+pub async fn run(mock: &MockTwitch, home: &Path, token: Option<&str>, args: &[&str]) -> Run {
+    let output = command(mock, home, token, args).output().await.unwrap();
+    Run::from(output)
+}
+
+/// Like [`run`], without `HOME` but with the variables in `env`, as on Windows
+pub async fn run_without_home(
+    mock: &MockTwitch,
+    token: Option<&str>,
+    args: &[&str],
+    env: &[(&str, &Path)],
+) -> Run {
+    let mut command = command(mock, Path::new(""), token, args);
+    command.env_remove("HOME").envs(env.iter().copied());
+    Run::from(command.output().await.unwrap())
+}
+
+/// Starts `prediction-creator --output json` like [`run`] without waiting for it,
+/// for commands that keep running until something happens
+pub fn spawn(
+    mock: &MockTwitch,
+    home: &Path,
+    token: Option<&str>,
+    args: &[&str],
+) -> tokio::process::Child {
+    command(mock, home, token, args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap()
+}
+
+fn command(
+    mock: &MockTwitch,
+    home: &Path,
+    token: Option<&str>,
+    args: &[&str],
+) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_prediction-creator"));
+    command
+        .args(["--output", "json"])
+        .args(args)
+        .env_clear()
+        .env("HOME", home)
+        .env("TWITCH_HELIX_URL", mock.helix_url())
+        .env("TWITCH_OAUTH2_URL", mock.auth_url())
+        .env("TWITCH_EVENTSUB_URL", mock.eventsub_url())
+        .env("TWITCH_IRC_URL", mock.irc_url())
+        .env("TWITCH_CLIENT_ID", CLIENT_ID);
+    if let Some(token) = token {
+        command.env("TWITCH_ACCESS_TOKEN", token);
+    }
+
+    command
+}
+
+impl From<Output> for Run {
+    fn from(output: Output) -> Self {
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        Run {
+            success: output.status.success(),
+            code: output.status.code(),
+            documents: stdout
+                .lines()
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()
+                .unwrap_or_default(),
+            stdout,
+            stderr: String::from_utf8(output.stderr).unwrap(),
+        }
+    }
+}
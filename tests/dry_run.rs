@@ -0,0 +1,165 @@
+mod common;
+
+use common::{run, MockTwitch, TOKEN};
+use serde_json::json;
+
+const CREATE: &[&str] = &[
+    "create",
+    "--title",
+    "Will we beat the boss?",
+    "--outcome",
+    "Yes",
+    "--outcome",
+    "No",
+];
+
+#[tokio::test]
+async fn create_without_any_request() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let dry_run = run(
+        &mock,
+        home.path(),
+        None,
+        &[CREATE, &["--dry-run", "--broadcaster-id", "999"]].concat(),
+    )
+    .await;
+    assert!(dry_run.success, "{}", dry_run.stderr);
+    assert_eq!(dry_run.last()["action"], "dry_run");
+    let request = &dry_run.last()["request"];
+    assert_eq!(request["method"], "POST");
+    assert_eq!(
+        request["endpoint"],
+        format!("{}predictions", mock.helix_url())
+    );
+    assert_eq!(
+        request["body"],
+        json!({
+            "broadcaster_id": "999",
+            "title": "Will we beat the boss?",
+            "outcomes": [{ "title": "Yes" }, { "title": "No" }],
+            "prediction_window": 30,
+        })
+    );
+    assert!(mock.requests().is_empty(), "{:?}", mock.requests());
+
+    // Without a broadcaster id, the token tells which channel the prediction is for
+    let dry_run = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[CREATE, &["--dry-run"]].concat(),
+    )
+    .await;
+    assert!(dry_run.success, "{}", dry_run.stderr);
+    assert_eq!(
+        dry_run.last()["request"]["body"]["broadcaster_id"],
+        common::BROADCASTER_ID
+    );
+    assert_eq!(mock.requests(), ["GET /oauth2/validate"]);
+    assert!(mock.predictions().is_empty());
+}
+
+#[tokio::test]
+async fn end_without_changing_the_prediction() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let created = run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    assert!(created.success, "{}", created.stderr);
+    let prediction = &created.last()["prediction"];
+
+    let dry_run = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resolve", "--dry-run", "--winner", "no"],
+    )
+    .await;
+    assert!(dry_run.success, "{}", dry_run.stderr);
+    let request = &dry_run.last()["request"];
+    assert_eq!(request["method"], "PATCH");
+    assert_eq!(
+        request["body"],
+        json!({
+            "broadcaster_id": common::BROADCASTER_ID,
+            "id": prediction["id"],
+            "status": "RESOLVED",
+            "winning_outcome_id": prediction["outcomes"][1]["id"],
+        })
+    );
+    assert_eq!(mock.predictions()[0]["status"], "ACTIVE");
+    assert!(!mock
+        .requests()
+        .contains(&"PATCH /helix/predictions".to_string()));
+
+    let dry_run = run(&mock, home.path(), Some(TOKEN), &["cancel", "--dry-run"]).await;
+    assert!(dry_run.success, "{}", dry_run.stderr);
+    assert_eq!(dry_run.last()["request"]["body"]["status"], "CANCELED");
+
+    let unsupported = run(&mock, home.path(), Some(TOKEN), &["status", "--dry-run"]).await;
+    assert!(!unsupported.success);
+    let offline = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["lock", "--dry-run", "--broadcaster-id", "999"],
+    )
+    .await;
+    assert_eq!(offline.code, Some(2), "{}", offline.stderr);
+    assert_eq!(mock.predictions()[0]["status"], "ACTIVE");
+}
+
+#[tokio::test]
+async fn end_offline_with_prediction_id() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let dry_run = run(
+        &mock,
+        home.path(),
+        None,
+        &[
+            "resolve",
+            "--dry-run",
+            "--broadcaster-id",
+            "999",
+            "--prediction-id",
+            "prediction-7",
+            "--winner",
+            "outcome-2",
+        ],
+    )
+    .await;
+    assert!(dry_run.success, "{}", dry_run.stderr);
+    assert_eq!(
+        dry_run.last()["request"]["body"],
+        json!({
+            "broadcaster_id": "999",
+            "id": "prediction-7",
+            "status": "RESOLVED",
+            "winning_outcome_id": "outcome-2",
+        })
+    );
+
+    for (command, status) in [("lock", "LOCKED"), ("cancel", "CANCELED")] {
+        let dry_run = run(
+            &mock,
+            home.path(),
+            None,
+            &[
+                command,
+                "--dry-run",
+                "--broadcaster-id",
+                "999",
+                "--prediction-id",
+                "prediction-7",
+            ],
+        )
+        .await;
+        assert!(dry_run.success, "{}", dry_run.stderr);
+        assert_eq!(dry_run.last()["request"]["body"]["status"], status);
+    }
+    assert!(mock.requests().is_empty(), "{:?}", mock.requests());
+}
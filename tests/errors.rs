@@ -0,0 +1,164 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{run, MockTwitch, TOKEN};
+
+#[tokio::test]
+async fn missing_token() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let status = run(&mock, home.path(), None, &["status"]).await;
+    assert_eq!(status.code, Some(3), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "no_token");
+}
+
+#[tokio::test]
+async fn invalid_token() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let status = run(&mock, home.path(), Some("not-a-token"), &["status"]).await;
+    assert_eq!(status.code, Some(4), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "invalid_token");
+}
+
+#[tokio::test]
+async fn invalid_arguments() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    for args in [
+        &["list", "--count", "0"][..],
+        &[
+            "create",
+            "--title",
+            "Title",
+            "--outcome",
+            "a",
+            "--outcome",
+            "b",
+            "--active-winner",
+            "1",
+        ],
+        &["status", "--dry-run"],
+        &["create", "--template", "missing"],
+    ] {
+        let run = run(&mock, home.path(), Some(TOKEN), args).await;
+        assert_eq!(run.code, Some(2), "{args:?}: {}", run.stderr);
+        assert_eq!(run.last()["error"]["code"], "invalid_arguments");
+    }
+}
+
+#[tokio::test]
+async fn scope_rejected_by_helix() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    mock.fail_with(
+        StatusCode::UNAUTHORIZED,
+        "Missing scope: channel:manage:predictions",
+    );
+
+    let status = run(&mock, home.path(), Some(TOKEN), &["status"]).await;
+    assert_eq!(status.code, Some(5), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "missing_scope");
+    assert_eq!(
+        status.last()["error"]["message"],
+        "The token is missing the required scopes: channel:manage:predictions. Run `prediction-creator login` to get a new token"
+    );
+}
+
+#[tokio::test]
+async fn not_affiliate() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    mock.fail_with(
+        StatusCode::FORBIDDEN,
+        "The broadcaster must be a partner or affiliate",
+    );
+
+    let status = run(&mock, home.path(), Some(TOKEN), &["status"]).await;
+    assert_eq!(status.code, Some(7), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "not_affiliate");
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    mock.fail_with(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests");
+
+    let status = run(&mock, home.path(), Some(TOKEN), &["status"]).await;
+    assert_eq!(status.code, Some(8), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "rate_limited");
+}
+
+#[tokio::test]
+async fn network_failure() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    // Nothing listens on port 1, so connecting is refused right away
+    let status = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["--api-base-url", "http://127.0.0.1:1/helix/", "status"],
+    )
+    .await;
+    assert_eq!(status.code, Some(9), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "network");
+}
+
+#[tokio::test]
+async fn no_active_prediction() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let canceled = run(&mock, home.path(), Some(TOKEN), &["cancel"]).await;
+    assert_eq!(canceled.code, Some(10), "{}", canceled.stderr);
+    assert_eq!(canceled.last()["error"]["code"], "no_active_prediction");
+}
+
+#[tokio::test]
+async fn channel_must_match_token() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let own = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["status", "--channel", "Mock_Broadcaster"],
+    )
+    .await;
+    assert!(own.success, "{}", own.stderr);
+
+    let other = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["status", "--channel", common::CO_STREAMER_LOGIN],
+    )
+    .await;
+    assert_eq!(other.code, Some(11), "{}", other.stderr);
+    assert_eq!(other.last()["error"]["code"], "wrong_channel");
+    assert!(
+        other.last()["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("mock_costreamer must run `prediction-creator login`"),
+        "{}",
+        other.stderr
+    );
+
+    let unknown = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["status", "--channel", "nobody_here"],
+    )
+    .await;
+    assert_eq!(unknown.code, Some(2), "{}", unknown.stderr);
+    assert_eq!(unknown.last()["error"]["code"], "unknown_channel");
+}
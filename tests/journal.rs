@@ -0,0 +1,152 @@
+mod common;
+
+use common::{run, run_without_home, MockTwitch, TOKEN};
+use serde_json::Value;
+
+const CREATE: &[&str] = &[
+    "create",
+    "--title",
+    "Will we beat the boss?",
+    "--outcome",
+    "Yes",
+    "--outcome",
+    "No",
+];
+
+fn journal(home: &std::path::Path) -> Vec<Value> {
+    let path = home.join(".local/state/prediction-creator/journal.jsonl");
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn records_actions_and_resumes() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let created = run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    assert!(created.success, "{}", created.stderr);
+    let prediction = &created.last()["prediction"];
+
+    let entries = journal(home.path());
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["action"], "created");
+    assert_eq!(entries[0]["prediction_id"], prediction["id"]);
+    assert_eq!(
+        entries[0]["outcomes"][1]["id"],
+        prediction["outcomes"][1]["id"]
+    );
+
+    let locked = run(&mock, home.path(), Some(TOKEN), &["lock"]).await;
+    assert!(locked.success, "{}", locked.stderr);
+
+    let resumed = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resume", "--winner", "2"],
+    )
+    .await;
+    assert!(resumed.success, "{}", resumed.stderr);
+    assert_eq!(resumed.last()["action"], "resolved");
+    assert_eq!(resumed.last()["prediction"]["id"], prediction["id"]);
+
+    let entries = journal(home.path());
+    let actions: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["created", "locked", "resolved"]);
+    assert_eq!(
+        entries[2]["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+
+    // Nothing is left to resume
+    let again = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resume", "--winner", "2"],
+    )
+    .await;
+    assert_eq!(again.code, Some(10), "{}", again.stderr);
+}
+
+#[tokio::test]
+async fn resume_reports_predictions_ended_elsewhere() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let created = run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    assert!(created.success, "{}", created.stderr);
+    mock.cancel_elsewhere();
+
+    let resumed = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resume", "--winner", "1"],
+    )
+    .await;
+    assert!(resumed.success, "{}", resumed.stderr);
+    assert_eq!(resumed.last()["action"], "ended_elsewhere");
+    assert_eq!(resumed.last()["prediction"]["status"], "CANCELED");
+}
+
+#[tokio::test]
+async fn unwritable_journal_only_warns() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    // A directory where the journal should be makes every write fail
+    std::fs::create_dir_all(
+        home.path()
+            .join(".local/state/prediction-creator/journal.jsonl"),
+    )
+    .unwrap();
+
+    let created = run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    assert!(created.success, "{}", created.stderr);
+    assert_eq!(created.documents[0]["action"], "warning");
+    assert_eq!(created.documents[0]["warning"]["code"], "journal");
+    assert_eq!(created.last()["action"], "created");
+    assert_eq!(mock.predictions().len(), 1);
+}
+
+#[tokio::test]
+async fn journal_is_skipped_without_a_place_to_keep_it() {
+    let mock = MockTwitch::start().await;
+
+    let status = run_without_home(&mock, Some(TOKEN), &["status"], &[]).await;
+    assert!(status.success, "{}", status.stderr);
+    assert_eq!(status.documents[0]["warning"]["code"], "journal");
+    assert_eq!(status.last()["action"], "status");
+
+    let created = run_without_home(&mock, Some(TOKEN), CREATE, &[]).await;
+    assert!(created.success, "{}", created.stderr);
+    assert_eq!(created.last()["action"], "created");
+    assert_eq!(mock.predictions().len(), 1);
+}
+
+#[tokio::test]
+async fn journal_falls_back_to_local_app_data() {
+    let mock = MockTwitch::start().await;
+    let local_app_data = tempfile::tempdir().unwrap();
+
+    let created = run_without_home(
+        &mock,
+        Some(TOKEN),
+        CREATE,
+        &[("LOCALAPPDATA", local_app_data.path())],
+    )
+    .await;
+    assert!(created.success, "{}", created.stderr);
+    assert_eq!(created.documents.len(), 1);
+    assert!(local_app_data
+        .path()
+        .join("prediction-creator/journal.jsonl")
+        .exists());
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::fs;
+
+use common::{run, MockTwitch};
+use serde_json::Value;
+
+#[tokio::test]
+async fn login_stores_token() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let login = run(&mock, home.path(), None, &["login"]).await;
+    assert!(login.success, "{}", login.stderr);
+    assert_eq!(login.last()["action"], "logged_in");
+    assert_eq!(login.last()["login"], common::BROADCASTER_LOGIN);
+
+    let path = home.path().join(".config/prediction-creator/token.json");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let status = run(&mock, home.path(), None, &["status"]).await;
+    assert!(status.success, "{}", status.stderr);
+    assert!(status.last()["prediction"].is_null());
+}
+
+#[tokio::test]
+async fn expiring_token_is_refreshed_before_ending() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let (access_token, refresh_token) = mock.issue_token(10);
+    let path = home.path().join(".config/prediction-creator/token.json");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(
+        &path,
+        serde_json::json!({
+            "client_id": common::CLIENT_ID,
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "login": common::BROADCASTER_LOGIN,
+            "user_id": common::BROADCASTER_ID,
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let created = run(
+        &mock,
+        home.path(),
+        None,
+        &[
+            "create",
+            "--title",
+            "Refresh?",
+            "--outcome",
+            "a",
+            "--outcome",
+            "b",
+        ],
+    )
+    .await;
+    assert!(created.success, "{}", created.stderr);
+
+    let canceled = run(&mock, home.path(), None, &["cancel"]).await;
+    assert!(canceled.success, "{}", canceled.stderr);
+
+    let stored: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_ne!(stored["access_token"], access_token);
+    assert!(mock.requests().contains(&"POST /oauth2/token".to_string()));
+}
+
+#[tokio::test]
+async fn expired_token_is_refreshed_before_validating() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let (access_token, refresh_token) = mock.issue_token(3600);
+    mock.expire_token(&access_token);
+    let path = home.path().join(".config/prediction-creator/token.json");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(
+        &path,
+        serde_json::json!({
+            "client_id": common::CLIENT_ID,
+            "access_token": access_token,
+            "refresh_token": refresh_token,
+            "login": common::BROADCASTER_LOGIN,
+            "user_id": common::BROADCASTER_ID,
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let status = run(&mock, home.path(), None, &["status"]).await;
+    assert!(status.success, "{}", status.stderr);
+
+    let stored: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_ne!(stored["access_token"], access_token);
+    assert_ne!(stored["refresh_token"], refresh_token);
+
+    let again = run(&mock, home.path(), None, &["status"]).await;
+    assert!(again.success, "{}", again.stderr);
+    let refreshes = mock
+        .requests()
+        .iter()
+        .filter(|request| *request == "POST /oauth2/token")
+        .count();
+    assert_eq!(refreshes, 1);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::{run, spawn, MockTwitch, TOKEN};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Reads the Server-Sent Events of the overlay feed
+struct Feed {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl Feed {
+    /// The prediction sent with the next event
+    async fn next(&mut self) -> Value {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let event: String = self.buffer.drain(..end + 2).collect();
+                if let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) {
+                    return serde_json::from_str::<Value>(data).unwrap()["prediction"].clone();
+                }
+                continue;
+            }
+            let chunk = self
+                .response
+                .chunk()
+                .await
+                .unwrap()
+                .expect("the feed to stay open");
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+}
+
+#[tokio::test]
+async fn follows_predictions_live() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let mut overlay = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["overlay", "--listen", "127.0.0.1:0"],
+    );
+    let mut documents = BufReader::new(overlay.stdout.take().unwrap()).lines();
+    let line = documents.next_line().await.unwrap().unwrap();
+    let listening: Value = serde_json::from_str(&line).unwrap();
+    let base = format!("http://{}", listening["address"].as_str().unwrap());
+
+    let page = reqwest::get(format!("{base}/")).await.unwrap();
+    assert!(page.text().await.unwrap().contains("EventSource"));
+
+    let mut feed = Feed {
+        response: reqwest::get(format!("{base}/events")).await.unwrap(),
+        buffer: String::new(),
+    };
+    assert_eq!(feed.next().await, Value::Null);
+
+    let created = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[
+            "create",
+            "--title",
+            "Boss fight?",
+            "--outcome",
+            "Win",
+            "--outcome",
+            "Lose",
+        ],
+    )
+    .await;
+    assert!(created.success, "{}", created.stderr);
+    let prediction = feed.next().await;
+    assert_eq!(prediction["title"], "Boss fight?");
+    assert_eq!(prediction["status"], "ACTIVE");
+    assert_eq!(prediction["outcomes"][1]["color"], "pink");
+
+    mock.predict(1, "viewer", 300);
+    mock.predict(0, "other_viewer", 100);
+    let mut prediction = feed.next().await;
+    while prediction["total_points"] != 400 {
+        prediction = feed.next().await;
+    }
+    assert_eq!(prediction["outcomes"][0]["percent"], 25.0);
+    assert_eq!(prediction["outcomes"][1]["percent"], 75.0);
+    assert_eq!(prediction["total_users"], 2);
+
+    let resolved = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resolve", "--winner", "2"],
+    )
+    .await;
+    assert!(resolved.success, "{}", resolved.stderr);
+    let prediction = feed.next().await;
+    assert_eq!(prediction["status"], "RESOLVED");
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+}
@@ -0,0 +1,402 @@
+mod common;
+
+use common::{run, MockTwitch, TOKEN, TOKEN_WITHOUT_SCOPES};
+
+const CREATE: &[&str] = &[
+    "create",
+    "--title",
+    "Will we beat the boss?",
+    "--outcome",
+    "Yes",
+    "--outcome",
+    "No",
+    "--prediction-window",
+    "120",
+];
+
+#[tokio::test]
+async fn create_then_find_active() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let created = run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    assert!(created.success, "{}", created.stderr);
+    assert_eq!(created.last()["action"], "created");
+    assert_eq!(created.last()["prediction"]["status"], "ACTIVE");
+    assert_eq!(created.last()["prediction"]["outcomes"][1]["title"], "No");
+
+    let again = run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    assert_eq!(again.code, Some(6), "{}", again.stderr);
+    assert_eq!(again.last()["error"]["code"], "prediction_already_active");
+
+    let adopted = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[CREATE, &["--on-active", "adopt"]].concat(),
+    )
+    .await;
+    assert!(adopted.success, "{}", adopted.stderr);
+    assert_eq!(adopted.last()["action"], "found_active");
+    assert_eq!(
+        adopted.last()["prediction"]["id"],
+        created.last()["prediction"]["id"]
+    );
+    assert_eq!(mock.predictions().len(), 1);
+
+    let status = run(&mock, home.path(), Some(TOKEN), &["status"]).await;
+    assert!(status.success, "{}", status.stderr);
+    assert_eq!(status.last()["action"], "status");
+    assert_eq!(
+        status.last()["prediction"]["title"],
+        "Will we beat the boss?"
+    );
+}
+
+#[tokio::test]
+async fn resolve_by_title() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    mock.predict(0, "alice", 100);
+    mock.predict(1, "bob", 300);
+
+    let resolved = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resolve", "--winner", "yes"],
+    )
+    .await;
+    assert!(resolved.success, "{}", resolved.stderr);
+    let prediction = &resolved.last()["prediction"];
+    assert_eq!(resolved.last()["action"], "resolved");
+    assert_eq!(prediction["status"], "RESOLVED");
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][0]["id"]
+    );
+    assert_eq!(
+        prediction["outcomes"][0]["top_predictors"][0]["channel_points_won"],
+        400
+    );
+
+    let status = run(&mock, home.path(), Some(TOKEN), &["status"]).await;
+    assert!(status.last()["prediction"].is_null());
+}
+
+#[tokio::test]
+async fn lock_then_resolve_by_number() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+
+    let locked = run(&mock, home.path(), Some(TOKEN), &["lock"]).await;
+    assert!(locked.success, "{}", locked.stderr);
+    assert_eq!(locked.last()["prediction"]["status"], "LOCKED");
+
+    let locked_again = run(&mock, home.path(), Some(TOKEN), &["lock"]).await;
+    assert!(!locked_again.success);
+    assert_eq!(locked_again.last()["action"], "error");
+
+    let resolved = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resolve", "--winner", "2"],
+    )
+    .await;
+    assert!(resolved.success, "{}", resolved.stderr);
+    let prediction = &resolved.last()["prediction"];
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+}
+
+#[tokio::test]
+async fn cancel() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+
+    let canceled = run(&mock, home.path(), Some(TOKEN), &["cancel"]).await;
+    assert!(canceled.success, "{}", canceled.stderr);
+    assert_eq!(canceled.last()["action"], "canceled");
+    assert_eq!(canceled.last()["prediction"]["status"], "CANCELED");
+
+    let nothing = run(&mock, home.path(), Some(TOKEN), &["cancel"]).await;
+    assert!(!nothing.success);
+}
+
+#[tokio::test]
+async fn resolve_by_non_ascii_title() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let created = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[
+            "create",
+            "--title",
+            "Wer gewinnt?",
+            "--outcome",
+            "Nein",
+            "--outcome",
+            "über",
+        ],
+    )
+    .await;
+    assert!(created.success, "{}", created.stderr);
+
+    let resolved = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resolve", "--winner", "ÜBER"],
+    )
+    .await;
+    assert!(resolved.success, "{}", resolved.stderr);
+    let prediction = &resolved.last()["prediction"];
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+}
+
+#[tokio::test]
+async fn unknown_winner_is_rejected() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+
+    let resolved = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["resolve", "--winner", "Maybe"],
+    )
+    .await;
+    assert!(!resolved.success);
+    assert_eq!(mock.predictions()[0]["status"], "ACTIVE");
+}
+
+#[tokio::test]
+async fn list_recent_predictions() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+    run(&mock, home.path(), Some(TOKEN), &["cancel"]).await;
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+
+    let listed = run(&mock, home.path(), Some(TOKEN), &["list"]).await;
+    assert!(listed.success, "{}", listed.stderr);
+    let predictions = listed.last()["predictions"].as_array().unwrap();
+    assert_eq!(predictions.len(), 2);
+    assert_eq!(predictions[0]["status"], "ACTIVE");
+    assert_eq!(predictions[1]["status"], "CANCELED");
+}
+
+#[tokio::test]
+async fn invalid_prediction_is_not_sent() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let created = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["create", "--title", "Too short", "--outcome", "Yes"],
+    )
+    .await;
+    assert_eq!(created.code, Some(2));
+    assert_eq!(created.last()["error"]["code"], "invalid_prediction");
+    assert_eq!(
+        created.last()["error"]["violations"][0]["field"],
+        "outcomes"
+    );
+    assert!(mock.requests().is_empty());
+
+    let duplicated = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[
+            "create",
+            "--title",
+            "Same twice",
+            "--outcome",
+            "über",
+            "--outcome",
+            "ÜBER",
+        ],
+    )
+    .await;
+    assert_eq!(duplicated.code, Some(2));
+    assert_eq!(
+        duplicated.last()["error"]["violations"][0]["field"],
+        "outcomes[2]"
+    );
+    assert!(mock.requests().is_empty());
+}
+
+#[tokio::test]
+async fn missing_scope_is_reported() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let status = run(&mock, home.path(), Some(TOKEN_WITHOUT_SCOPES), &["status"]).await;
+    assert_eq!(status.code, Some(5));
+    let message = status.last()["error"]["message"].as_str().unwrap();
+    assert!(message.contains("channel:manage:predictions"), "{message}");
+}
+
+#[tokio::test]
+async fn history_follows_the_cursor() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    for day in 1..=28 {
+        mock.add_resolved_prediction(
+            &format!("Day {day}, with a comma"),
+            &format!("2024-02-{day:02}T20:00:00Z"),
+        );
+    }
+
+    let history = run(&mock, home.path(), Some(TOKEN), &["history"]).await;
+    assert!(history.success, "{}", history.stderr);
+    let predictions = history.last()["predictions"].as_array().unwrap();
+    assert_eq!(predictions.len(), 28);
+    assert_eq!(predictions[27]["title"], "Day 1, with a comma");
+
+    let since = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["history", "--since", "2024-02-20"],
+    )
+    .await;
+    assert_eq!(since.last()["predictions"].as_array().unwrap().len(), 9);
+
+    // A full page of 25 takes a single request
+    let before = mock.requests().len();
+    let limited = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["history", "--limit", "25"],
+    )
+    .await;
+    assert_eq!(limited.last()["predictions"].as_array().unwrap().len(), 25);
+    let pages = mock.requests()[before..]
+        .iter()
+        .filter(|request| *request == "GET /helix/predictions")
+        .count();
+    assert_eq!(pages, 1);
+
+    let csv = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["history", "--limit", "2", "--format", "csv"],
+    )
+    .await;
+    assert!(csv.success, "{}", csv.stderr);
+    assert_eq!(
+        csv.stdout.lines().collect::<Vec<_>>(),
+        [
+            "id,created_at,title,status,winner,total_points,participants",
+            "prediction-28,2024-02-28T20:00:00Z,\"Day 28, with a comma\",RESOLVED,Yes,200,2",
+            "prediction-27,2024-02-27T20:00:00Z,\"Day 27, with a comma\",RESOLVED,Yes,200,2",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn cancel_and_replace_active_prediction() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+
+    let replaced = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[
+            "create",
+            "--title",
+            "Next map?",
+            "--outcome",
+            "Dust",
+            "--outcome",
+            "Mirage",
+            "--on-active",
+            "cancel-and-replace",
+        ],
+    )
+    .await;
+    assert!(replaced.success, "{}", replaced.stderr);
+    assert_eq!(replaced.documents[0]["action"], "canceled");
+    assert_eq!(replaced.documents[1]["action"], "created");
+
+    let predictions = mock.predictions();
+    assert_eq!(predictions[0]["title"], "Next map?");
+    assert_eq!(predictions[0]["status"], "ACTIVE");
+    assert_eq!(predictions[1]["status"], "CANCELED");
+}
+
+#[tokio::test]
+async fn resolve_then_replace_active_prediction() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    run(&mock, home.path(), Some(TOKEN), CREATE).await;
+
+    // There is no terminal to ask for the winner on
+    let unattended = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[CREATE, &["--on-active", "resolve-then-replace"]].concat(),
+    )
+    .await;
+    assert_eq!(unattended.code, Some(2), "{}", unattended.stderr);
+    assert_eq!(unattended.last()["error"]["code"], "invalid_arguments");
+    assert_eq!(mock.predictions().len(), 1);
+    assert_eq!(mock.predictions()[0]["status"], "ACTIVE");
+
+    let replaced = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[
+            CREATE,
+            &[
+                "--on-active",
+                "resolve-then-replace",
+                "--active-winner",
+                "no",
+            ],
+        ]
+        .concat(),
+    )
+    .await;
+    assert!(replaced.success, "{}", replaced.stderr);
+    assert_eq!(replaced.documents[0]["action"], "resolved");
+    assert_eq!(replaced.documents[1]["action"], "created");
+
+    let predictions = mock.predictions();
+    assert_eq!(predictions[0]["status"], "ACTIVE");
+    assert_eq!(predictions[1]["status"], "RESOLVED");
+    assert_eq!(
+        predictions[1]["winning_outcome_id"],
+        predictions[1]["outcomes"][1]["id"]
+    );
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{run, MockTwitch, CO_STREAMER_ID, CO_STREAMER_TOKEN};
+
+/// Stores `token` for the profile called `name`, as `login --profile` does
+fn add_profile(home: &Path, name: &str, token: &str, login: &str, user_id: &str) {
+    let dir = home.join(".config/prediction-creator");
+    fs::create_dir_all(dir.join("profiles")).unwrap();
+    fs::write(
+        dir.join("profiles").join(format!("{name}.json")),
+        serde_json::json!({
+            "client_id": common::CLIENT_ID,
+            "access_token": token,
+            "refresh_token": null,
+            "login": login,
+            "user_id": user_id,
+        })
+        .to_string(),
+    )
+    .unwrap();
+}
+
+#[tokio::test]
+async fn manages_several_channels() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let login = run(&mock, home.path(), None, &["login", "--profile", "main"]).await;
+    assert!(login.success, "{}", login.stderr);
+    assert_eq!(login.last()["profile"], "main");
+    let config = home.path().join(".config/prediction-creator");
+    assert!(config.join("profiles/main.json").exists());
+    assert!(!config.join("token.json").exists());
+
+    add_profile(
+        home.path(),
+        "costream",
+        CO_STREAMER_TOKEN,
+        common::CO_STREAMER_LOGIN,
+        CO_STREAMER_ID,
+    );
+    let mut profiles = fs::read_to_string(config.join("profiles.toml")).unwrap();
+    profiles.push_str(&format!(
+        "\n[costream]\nlogin = \"{}\"\nbroadcaster_id = \"{CO_STREAMER_ID}\"\n",
+        common::CO_STREAMER_LOGIN
+    ));
+    fs::write(config.join("profiles.toml"), profiles).unwrap();
+
+    let created = run(
+        &mock,
+        home.path(),
+        None,
+        &[
+            "create",
+            "--profile",
+            "costream",
+            "--title",
+            "Co-stream bet",
+            "--outcome",
+            "a",
+            "--outcome",
+            "b",
+        ],
+    )
+    .await;
+    assert!(created.success, "{}", created.stderr);
+    assert_eq!(
+        created.last()["prediction"]["broadcaster_id"],
+        CO_STREAMER_ID
+    );
+
+    let status = run(&mock, home.path(), None, &["status", "--profile", "main"]).await;
+    assert!(status.success, "{}", status.stderr);
+    assert!(status.last()["prediction"].is_null());
+
+    let all = run(&mock, home.path(), None, &["status", "--all-profiles"]).await;
+    assert!(all.success, "{}", all.stderr);
+    let profiles = all.last()["profiles"].as_array().unwrap();
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0]["profile"], "costream");
+    assert_eq!(profiles[0]["prediction"]["title"], "Co-stream bet");
+    assert_eq!(profiles[1]["profile"], "main");
+    assert!(profiles[1]["prediction"].is_null());
+}
+
+#[tokio::test]
+async fn rejects_tokens_for_another_channel() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    // The profile manages the mock broadcaster, but its token is the co-streamer's
+    add_profile(
+        home.path(),
+        "main",
+        CO_STREAMER_TOKEN,
+        common::CO_STREAMER_LOGIN,
+        CO_STREAMER_ID,
+    );
+    fs::write(
+        home.path().join(".config/prediction-creator/profiles.toml"),
+        format!(
+            "[main]\nlogin = \"{}\"\nbroadcaster_id = \"{}\"\n",
+            common::BROADCASTER_LOGIN,
+            common::BROADCASTER_ID
+        ),
+    )
+    .unwrap();
+
+    let status = run(&mock, home.path(), None, &["status", "--profile", "main"]).await;
+    assert_eq!(status.code, Some(4), "{}", status.stderr);
+    assert_eq!(status.last()["error"]["code"], "invalid_token");
+
+    // Listing every profile reports the failure instead of giving up
+    let all = run(&mock, home.path(), None, &["status", "--all-profiles"]).await;
+    assert!(all.success, "{}", all.stderr);
+    assert_eq!(all.last()["profiles"][0]["error"]["code"], "invalid_token");
+
+    let unknown = run(&mock, home.path(), None, &["status", "--profile", "nope"]).await;
+    assert_eq!(unknown.code, Some(2), "{}", unknown.stderr);
+    assert_eq!(unknown.last()["error"]["code"], "unknown_profile");
+    assert!(
+        unknown.last()["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Available profiles are: main"),
+        "{}",
+        unknown.stderr
+    );
+
+    // A profile that is known but was never logged in to has no token
+    fs::remove_file(
+        home.path()
+            .join(".config/prediction-creator/profiles/main.json"),
+    )
+    .unwrap();
+    let missing = run(&mock, home.path(), None, &["status", "--profile", "main"]).await;
+    assert_eq!(missing.code, Some(3), "{}", missing.stderr);
+    assert_eq!(missing.last()["error"]["code"], "no_token");
+}
+
+#[tokio::test]
+async fn login_checks_the_profile_name_before_authorizing() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let login = run(
+        &mock,
+        home.path(),
+        None,
+        &["login", "--profile", "my channel"],
+    )
+    .await;
+    assert_eq!(login.code, Some(2), "{}", login.stderr);
+    assert_eq!(login.last()["error"]["code"], "invalid_arguments");
+    assert!(mock.requests().is_empty(), "{:?}", mock.requests());
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{spawn, MockTwitch, TOKEN};
+use prediction_creator::queue::Queue;
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const QUEUE: &str = r#"
+[[prediction]]
+title = "Who wins round 1?"
+outcomes = ["Red", "Blue"]
+prediction_window = 120
+
+[[prediction]]
+title = "Who wins round 2?"
+outcomes = ["Red", "Blue"]
+
+[[prediction]]
+title = "Who wins the final?"
+outcomes = ["Red", "Blue"]
+"#;
+
+#[tokio::test]
+async fn runs_in_order_and_resumes() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("finals.toml");
+    std::fs::write(&file, QUEUE).unwrap();
+    let args = ["queue", file.to_str().unwrap()];
+
+    let queue = spawn(&mock, home.path(), Some(TOKEN), &args);
+    mock.wait_for_status("ACTIVE").await;
+    assert_eq!(mock.predictions()[0]["title"], "Who wins round 1?");
+
+    // The next prediction starts as soon as a moderator ends the running one
+    mock.cancel_elsewhere();
+    for _ in 0..100 {
+        if mock.predictions().len() == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    mock.wait_for_status("ACTIVE").await;
+    assert_eq!(mock.predictions()[0]["title"], "Who wins round 2?");
+
+    // Stopping the queue and running it again picks the running prediction back up
+    drop(queue);
+    let mut resumed = spawn(&mock, home.path(), Some(TOKEN), &args);
+    let mut documents = BufReader::new(resumed.stdout.take().unwrap()).lines();
+    let mut next_action = async || {
+        let line = documents.next_line().await.unwrap().unwrap();
+        serde_json::from_str::<Value>(&line).unwrap()["action"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(next_action().await, "resumed");
+    mock.cancel_elsewhere();
+    assert_eq!(next_action().await, "ended_elsewhere");
+    assert_eq!(next_action().await, "created");
+    mock.cancel_elsewhere();
+    assert_eq!(next_action().await, "ended_elsewhere");
+    assert_eq!(next_action().await, "queue_finished");
+
+    let status = resumed.wait().await.unwrap();
+    assert!(status.success());
+    let titles: Vec<String> = mock
+        .predictions()
+        .iter()
+        .map(|prediction| prediction["title"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(
+        titles,
+        [
+            "Who wins the final?",
+            "Who wins round 2?",
+            "Who wins round 1?"
+        ]
+    );
+}
+
+#[test]
+fn skips_and_reorders_persistently() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("finals.toml");
+    std::fs::write(&file, QUEUE).unwrap();
+
+    let mut queue = Queue::load(&file, false).unwrap();
+    assert_eq!(queue.next_up().unwrap().title, "Who wins round 1?");
+    queue.start("prediction-1".into()).unwrap();
+    queue.reorder(&[1, 0]).unwrap();
+    assert_eq!(queue.next_up().unwrap().title, "Who wins the final?");
+    assert_eq!(queue.skip().unwrap().unwrap().title, "Who wins the final?");
+
+    // Another run continues where this one stopped
+    let mut queue = Queue::load(&file, false).unwrap();
+    let (running, id) = queue.running().unwrap();
+    assert_eq!(running.title, "Who wins round 1?");
+    assert_eq!(id.as_str(), "prediction-1");
+    queue.finish().unwrap();
+    assert_eq!(queue.next_up().unwrap().title, "Who wins round 2?");
+    assert_eq!(queue.upcoming().count(), 1);
+
+    let queue = Queue::load(&file, true).unwrap();
+    assert!(queue.running().is_none());
+    assert_eq!(queue.upcoming().count(), 3);
+}
+
+#[test]
+fn rejects_invalid_predictions() {
+    let home = tempfile::tempdir().unwrap();
+    let file = home.path().join("finals.toml");
+    std::fs::write(
+        &file,
+        "[[prediction]]\ntitle = \"Only one outcome\"\noutcomes = [\"Yes\"]\n",
+    )
+    .unwrap();
+
+    let error = Queue::load(&file, false).unwrap_err();
+    assert!(
+        error.to_string().starts_with("Prediction 1 in"),
+        "{error:#}"
+    );
+}
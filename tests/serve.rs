@@ -0,0 +1,147 @@
+mod common;
+
+use common::{spawn, MockTwitch, TOKEN};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const SECRET: &str = "correct horse battery staple";
+
+#[tokio::test]
+async fn manages_predictions_over_http() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+
+    let mut server = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["serve", "--listen", "127.0.0.1:0", "--secret", SECRET],
+    );
+    let mut documents = BufReader::new(server.stdout.take().unwrap()).lines();
+    let line = documents.next_line().await.unwrap().unwrap();
+    let listening: Value = serde_json::from_str(&line).unwrap();
+    let base = format!("http://{}", listening["address"].as_str().unwrap());
+
+    let client = reqwest::Client::new();
+    let send = async |method: reqwest::Method, path: &str, body: Option<Value>| {
+        let mut request = client
+            .request(method, format!("{base}{path}"))
+            .header("X-Prediction-Secret", SECRET);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        (
+            response.status().as_u16(),
+            response.json::<Value>().await.unwrap(),
+        )
+    };
+
+    // Other local processes don't know the secret
+    let unauthorized = client
+        .post(format!("{base}/predictions/current/cancel"))
+        .header("X-Prediction-Secret", "guess")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unauthorized.status().as_u16(), 401);
+
+    let (status, body) = send(reqwest::Method::GET, "/predictions/current", None).await;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["code"], "no_active_prediction");
+
+    let (status, body) = send(
+        reqwest::Method::POST,
+        "/predictions",
+        Some(json!({ "title": "Will we beat the boss?", "outcomes": ["Yes", "No"] })),
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["action"], "created");
+    assert_eq!(body["prediction"]["prediction_window"], 30);
+
+    let (status, body) = send(
+        reqwest::Method::POST,
+        "/predictions",
+        Some(json!({ "title": "", "outcomes": ["Yes"] })),
+    )
+    .await;
+    assert_eq!(status, 422, "{body}");
+    assert_eq!(body["error"]["code"], "invalid_prediction");
+
+    let (status, body) = send(reqwest::Method::POST, "/predictions/current/lock", None).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["prediction"]["status"], "LOCKED");
+    let (status, body) = send(reqwest::Method::POST, "/predictions/current/lock", None).await;
+    assert_eq!(status, 409, "{body}");
+    assert_eq!(body["error"]["code"], "already_locked");
+
+    let (status, body) = send(
+        reqwest::Method::POST,
+        "/predictions/current/resolve",
+        Some(json!({ "outcome": "Maybe" })),
+    )
+    .await;
+    assert_eq!(status, 422, "{body}");
+    assert_eq!(body["error"]["code"], "unknown_outcome");
+
+    let (status, body) = send(
+        reqwest::Method::POST,
+        "/predictions/current/resolve",
+        Some(json!({ "outcome": "no" })),
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["action"], "resolved");
+    let prediction = &mock.predictions()[0];
+    assert_eq!(prediction["status"], "RESOLVED");
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+}
+
+#[tokio::test]
+async fn reports_an_unwritable_journal_once() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    // A directory where the journal should be makes every write fail
+    std::fs::create_dir_all(
+        home.path()
+            .join(".local/state/prediction-creator/journal.jsonl"),
+    )
+    .unwrap();
+
+    let mut server = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &["serve", "--listen", "127.0.0.1:0", "--secret", SECRET],
+    );
+    let mut documents = BufReader::new(server.stdout.take().unwrap()).lines();
+    let mut next_document = async || -> Value {
+        let line = documents.next_line().await.unwrap().unwrap();
+        serde_json::from_str(&line).unwrap()
+    };
+    let listening = next_document().await;
+    let base = format!("http://{}", listening["address"].as_str().unwrap());
+
+    let response = reqwest::Client::new()
+        .post(format!("{base}/predictions"))
+        .header("X-Prediction-Secret", SECRET)
+        .json(&json!({ "title": "Will we beat the boss?", "outcomes": ["Yes", "No"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["action"], "created");
+    assert!(body["warning"].is_string(), "{body}");
+
+    let warning = next_document().await;
+    assert_eq!(warning["action"], "warning");
+    assert_eq!(warning["warning"]["code"], "journal");
+    let created = next_document().await;
+    assert_eq!(created["action"], "created");
+    assert!(created.get("warning").is_none(), "{created}");
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use common::{run, spawn, MockTwitch, Run, TOKEN};
+
+const WATCH: &[&str] = &[
+    "watch",
+    "--title",
+    "Will we beat the boss?",
+    "--outcome",
+    "Yes",
+    "--outcome",
+    "No",
+    "--prediction-window",
+    "120",
+];
+
+#[tokio::test]
+async fn resolves_from_tailed_file() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    let events = home.path().join("events.log");
+    std::fs::write(&events, "cancel\n").unwrap();
+
+    let watch = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[WATCH, &["--file", events.to_str().unwrap()]].concat(),
+    );
+    mock.wait_for_status("ACTIVE").await;
+
+    let mut file = OpenOptions::new().append(true).open(&events).unwrap();
+    writeln!(file, "Maybe").unwrap();
+    writeln!(file, "no").unwrap();
+
+    let watched = Run::from(watch.wait_with_output().await.unwrap());
+    assert!(watched.success, "{}", watched.stderr);
+    assert!(
+        watched.stderr.contains("Ignoring message"),
+        "{}",
+        watched.stderr
+    );
+    assert_eq!(watched.last()["action"], "resolved");
+    let prediction = &mock.predictions()[0];
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][1]["id"]
+    );
+}
+
+#[tokio::test]
+async fn cancels_from_webhook() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let watch = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[WATCH, &["--webhook", &addr.to_string()]].concat(),
+    );
+    mock.wait_for_status("ACTIVE").await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{addr}/"))
+        .body("cancel")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+
+    let watched = Run::from(watch.wait_with_output().await.unwrap());
+    assert!(watched.success, "{}", watched.stderr);
+    assert_eq!(watched.last()["action"], "canceled");
+    assert_eq!(mock.predictions()[0]["status"], "CANCELED");
+}
+
+#[tokio::test]
+async fn cancels_after_timeout() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    let events = home.path().join("events.log");
+
+    let watched = run(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[
+            WATCH,
+            &["--file", events.to_str().unwrap(), "--timeout", "1"],
+        ]
+        .concat(),
+    )
+    .await;
+    assert!(watched.success, "{}", watched.stderr);
+    assert_eq!(watched.documents[0]["action"], "created");
+    assert_eq!(watched.last()["action"], "canceled");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn resolves_from_named_pipe() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    let pipe = home.path().join("events");
+    let status = std::process::Command::new("mkfifo")
+        .arg(&pipe)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let watch = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[WATCH, &["--pipe", pipe.to_str().unwrap()]].concat(),
+    );
+    mock.wait_for_status("ACTIVE").await;
+
+    // Opening the pipe for writing blocks until the reader has it open
+    tokio::task::spawn_blocking(move || {
+        let mut writer = OpenOptions::new().write(true).open(&pipe).unwrap();
+        writeln!(writer, "1").unwrap();
+    })
+    .await
+    .unwrap();
+
+    let watched = Run::from(watch.wait_with_output().await.unwrap());
+    assert!(watched.success, "{}", watched.stderr);
+    assert_eq!(watched.last()["action"], "resolved");
+    let prediction = &mock.predictions()[0];
+    assert_eq!(
+        prediction["winning_outcome_id"],
+        prediction["outcomes"][0]["id"]
+    );
+}
+
+#[tokio::test]
+async fn stops_when_ended_elsewhere() {
+    let mock = MockTwitch::start().await;
+    let home = tempfile::tempdir().unwrap();
+    let events = home.path().join("events.log");
+
+    let watch = spawn(
+        &mock,
+        home.path(),
+        Some(TOKEN),
+        &[WATCH, &["--file", events.to_str().unwrap()]].concat(),
+    );
+    mock.wait_for_status("ACTIVE").await;
+    assert_eq!(
+        mock.subscriptions(),
+        [
+            "channel.prediction.begin",
+            "channel.prediction.progress",
+            "channel.prediction.lock",
+            "channel.prediction.end",
+        ]
+    );
+
+    mock.cancel_elsewhere();
+
+    let watched = Run::from(watch.wait_with_output().await.unwrap());
+    assert!(watched.success, "{}", watched.stderr);
+    assert_eq!(watched.last()["action"], "ended_elsewhere");
+    assert_eq!(watched.last()["prediction"]["status"], "CANCELED");
+    assert!(!mock
+        .requests()
+        .contains(&"PATCH /helix/predictions".to_string()));
+}